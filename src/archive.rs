@@ -0,0 +1,114 @@
+use std::{error::Error as StdError, fs, path::Path};
+
+use opencv::videoio::{self, VideoCaptureTraitConst};
+
+use crate::{
+    config::{ArchiveAction, ArchiveTier},
+    segments::{
+        cam_recordings_dir, segment_sidecar_paths, write_segment_meta, Segment, SegmentMeta,
+    },
+    utils::video::reencode_video,
+};
+
+/// Extension re-encoded segments are written with until they replace the original
+pub const REENCODE_TMP_EXTENSION: &str = "reencode.tmp";
+
+/// Segments finalized before sidecars existed get one made up from the video itself
+fn probe_segment_meta(segment: &Segment) -> Result<SegmentMeta, Box<dyn StdError>> {
+    let vid_cap =
+        videoio::VideoCapture::from_file(segment.path.to_str().unwrap(), videoio::CAP_ANY)?;
+
+    Ok(SegmentMeta {
+        cam_idx: segment.cam_idx,
+        start: segment.start,
+        end: segment.end(),
+        frame_count: vid_cap.get(videoio::CAP_PROP_FRAME_COUNT)? as usize,
+        frame_rate: vid_cap.get(videoio::CAP_PROP_FPS)?.round() as usize,
+        archive_tier: None,
+        tags: Vec::new(),
+    })
+}
+
+/// Renames, or copies and deletes if the destination is on another filesystem
+fn move_file(from: &Path, to: &Path) -> Result<(), Box<dyn StdError>> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+
+    Ok(())
+}
+
+/// Applies an archive tier to a segment and records it in the segment's sidecar
+pub fn archive_segment(
+    segment: &Segment,
+    tier_idx: usize,
+    tier: &ArchiveTier,
+) -> Result<(), Box<dyn StdError>> {
+    let mut meta = match &segment.meta {
+        Some(meta) => meta.clone(),
+        None => probe_segment_meta(segment)?,
+    };
+
+    match &tier.action {
+        ArchiveAction::Reencode {
+            max_width,
+            fps,
+            bitrate_kbps,
+        } => {
+            // Frames are only ever dropped, never made up
+            let fps = fps.filter(|fps| *fps > 0 && *fps < meta.frame_rate);
+
+            // Written next to the segment and swapped in once done, so a failed encode leaves
+            // the original alone
+            let reencoded_path = segment.path.with_extension(REENCODE_TMP_EXTENSION);
+            reencode_video(
+                &segment.path,
+                &reencoded_path,
+                *max_width,
+                fps,
+                *bitrate_kbps,
+            )?;
+            fs::rename(&reencoded_path, &segment.path)?;
+
+            if let Some(fps) = fps {
+                meta.frame_count = meta.frame_count * fps / meta.frame_rate;
+                meta.frame_rate = fps;
+            }
+
+            meta.archive_tier = Some(tier_idx);
+            write_segment_meta(&segment.path, &meta)?;
+        }
+        ArchiveAction::Move { dir } => {
+            let archive_cam_dir = cam_recordings_dir(dir, segment.cam_idx);
+
+            // Already moved by a run which was interrupted before the tier was recorded
+            if segment.path.parent() == Some(archive_cam_dir.as_path()) {
+                meta.archive_tier = Some(tier_idx);
+                return write_segment_meta(&segment.path, &meta);
+            }
+
+            meta.archive_tier = Some(tier_idx);
+            write_segment_meta(&segment.path, &meta)?;
+
+            fs::create_dir_all(&archive_cam_dir)?;
+
+            // The video goes last so the segment only shows up in the archive once complete
+            let sidecar_paths = segment_sidecar_paths(&segment.path)
+                .into_iter()
+                .filter(|p| p.exists());
+
+            for path in sidecar_paths.chain([segment.path.clone()]) {
+                move_file(&path, &archive_cam_dir.join(path.file_name().unwrap()))?;
+            }
+        }
+    }
+
+    println!(
+        "Archived segment {:?} with tier {}",
+        segment.path,
+        tier_idx + 1
+    );
+
+    Ok(())
+}
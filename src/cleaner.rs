@@ -1,41 +1,508 @@
-use std::{error::Error as StdError, fs, path::PathBuf, thread, time::Duration};
+use std::{
+    error::Error as StdError,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    thread,
+    time::Duration,
+};
 
-use crate::config::Config;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
 
-fn clean_files_older_than(path: &PathBuf, duration: Duration) -> Result<(), Box<dyn StdError>> {
-    for dir_entry in fs::read_dir(path)? {
-        let dir_entry = dir_entry?;
+use crate::{
+    archive::{archive_segment, REENCODE_TMP_EXTENSION},
+    config::{Config, SegmentTag, CONFIG_PATH},
+    events::{log_event, EventKind},
+    locks::{is_locked, load_locks, locked_usage, Lock},
+    segments::{
+        list_all_segments, list_cam_dirs, list_segments, parse_segment_name_prefix, segment_roots,
+        segment_sidecar_paths, Segment,
+    },
+    snapshots::{cam_interval_snapshots_dir, parse_snapshot_time},
+};
 
-        if let Ok(file_type) = dir_entry.file_type() {
-            let dir_entry_path = dir_entry.path();
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60 * 6);
 
-            if file_type.is_dir() {
-                clean_files_older_than(&dir_entry_path, duration)?;
-            } else if file_type.is_file() {
-                let file_is_expired = dir_entry
-                    .metadata()
-                    .is_ok_and(|m| m.created().is_ok_and(|c| c.elapsed().unwrap() > duration));
+pub static CLEANER_AUDIT_PATH: LazyLock<Box<Path>> = LazyLock::new(|| {
+    CONFIG_PATH
+        .parent()
+        .unwrap()
+        .join("cleaner-audit.jsonl")
+        .into_boxed_path()
+});
 
-                if file_is_expired {
-                    fs::remove_file(dir_entry_path)?;
+/// Serializes appends so entries from the cleaner and the disk guard don't interleave
+static CLEANER_AUDIT_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Keeps the cleaner from clearing out staged segments while the disk guard is staging them
+static DELETION_STAGING_LOCK: Mutex<()> = Mutex::new(());
+
+/// Why the cleaner deleted something
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanRule {
+    Retention,
+    LowDiskSpace,
+    UnencodedFrames,
+    OrphanedSidecar,
+    UnfinishedReencode,
+    SnapshotRetention,
+}
+
+impl CleanRule {
+    pub fn label(self) -> &'static str {
+        match self {
+            CleanRule::Retention => "retention",
+            CleanRule::LowDiskSpace => "low disk space",
+            CleanRule::UnencodedFrames => "unencoded frames past retention",
+            CleanRule::OrphanedSidecar => "orphaned sidecar",
+            CleanRule::UnfinishedReencode => "unfinished re-encode",
+            CleanRule::SnapshotRetention => "snapshot retention",
+        }
+    }
+}
+
+/// A file or directory the cleaner deleted, or would delete in a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deletion {
+    pub at: DateTime<Local>,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Age of the footage going by the time in its name
+    pub age_secs: i64,
+    pub rule: CleanRule,
+}
+
+impl Deletion {
+    fn new(path: &Path, created_at: DateTime<Local>, rule: CleanRule) -> Self {
+        Self {
+            at: Local::now(),
+            path: path.to_path_buf(),
+            size_bytes: path_size(path),
+            age_secs: (Local::now() - created_at).num_seconds(),
+            rule,
+        }
+    }
+}
+
+/// Where a camera's segments are moved to while they're being deleted, one directory each
+fn deletion_staging_dir(cam_dir: &Path) -> PathBuf {
+    cam_dir.join(".deleting")
+}
+
+fn append_audit_entry(deletion: &Deletion) -> Result<(), Box<dyn StdError>> {
+    let _lock = CLEANER_AUDIT_FILE_LOCK.lock().unwrap();
+
+    fs::create_dir_all(CLEANER_AUDIT_PATH.parent().unwrap())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&*CLEANER_AUDIT_PATH)?;
+    writeln!(file, "{}", serde_json::to_string(deletion)?)?;
+
+    Ok(())
+}
+
+fn path_size(path: &Path) -> u64 {
+    if !path.is_dir() {
+        return fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    }
+
+    fs::read_dir(path)
+        .map(|dir_entries| {
+            dir_entries
+                .filter_map(|dir_entry| dir_entry.ok())
+                .map(|dir_entry| path_size(&dir_entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// How long a segment is kept for going by its tags, `None` if it's kept until unlocked
+fn segment_retention_hours(config: &Config, segment: &Segment, locked: bool) -> Option<u64> {
+    let tag_retention_hours = &config.cleaner.tag_retention_hours;
+
+    if locked && !tag_retention_hours.contains_key(&SegmentTag::Locked) {
+        return None;
+    }
+
+    let tags = segment.meta.iter().flat_map(|m| m.tags.iter());
+
+    tags.chain(locked.then_some(&SegmentTag::Locked))
+        .filter_map(|tag| tag_retention_hours.get(tag).copied())
+        .max()
+        .or(Some(config.cleaner.retention_hours))
+}
+
+/// Does the actual deleting, or only keeps track of what would be deleted in a dry run
+pub struct Cleaner {
+    dry_run: bool,
+    deletions: Vec<Deletion>,
+}
+
+impl Cleaner {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            deletions: Vec::new(),
+        }
+    }
+
+    pub fn deletions(&self) -> &[Deletion] {
+        &self.deletions
+    }
+
+    pub fn freed_bytes(&self) -> u64 {
+        self.deletions.iter().map(|d| d.size_bytes).sum()
+    }
+
+    /// Logs and audits a deletion which was carried out, or only keeps track of it in a dry run
+    fn record_deletion(&mut self, cam_idx: Option<i32>, deletion: Deletion) {
+        if !self.dry_run {
+            log_event(
+                EventKind::CleanerDeletion,
+                cam_idx,
+                format!(
+                    "{} ({})",
+                    deletion.path.to_string_lossy(),
+                    deletion.rule.label()
+                ),
+            );
+
+            if let Err(error) = append_audit_entry(&deletion) {
+                println!("Failed to write cleaner audit log due to error: {error}");
+            }
+        }
+
+        self.deletions.push(deletion);
+    }
+
+    fn delete(
+        &mut self,
+        cam_idx: Option<i32>,
+        path: &Path,
+        created_at: DateTime<Local>,
+        rule: CleanRule,
+    ) -> Result<(), Box<dyn StdError>> {
+        let deletion = Deletion::new(path, created_at, rule);
+
+        if !self.dry_run {
+            match path.is_dir() {
+                true => fs::remove_dir_all(path)?,
+                false => fs::remove_file(path)?,
+            }
+        }
+
+        self.record_deletion(cam_idx, deletion);
+
+        Ok(())
+    }
+
+    /// Deletes a segment's video together with its sidecars. They're all moved out of the way
+    /// first so the segment disappears at once, a staging directory left behind by a failure is
+    /// removed on the next run.
+    fn delete_segment(
+        &mut self,
+        segment: &Segment,
+        rule: CleanRule,
+    ) -> Result<(), Box<dyn StdError>> {
+        let paths = [segment.path.clone()]
+            .into_iter()
+            .chain(segment_sidecar_paths(&segment.path))
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+
+        let deletions = paths
+            .iter()
+            .map(|path| Deletion::new(path, segment.start, rule))
+            .collect::<Vec<_>>();
+
+        if !self.dry_run {
+            let _lock = DELETION_STAGING_LOCK.lock().unwrap();
+
+            let staging_dir = deletion_staging_dir(segment.path.parent().unwrap())
+                .join(segment.path.file_stem().unwrap());
+            fs::create_dir_all(&staging_dir)?;
+
+            // The video goes first so a segment is never listed with only part of its files left
+            for path in &paths {
+                fs::rename(path, staging_dir.join(path.file_name().unwrap()))?;
+            }
+
+            fs::remove_dir_all(&staging_dir)?;
+
+            // Only succeeds if no other segment is being deleted, so the camera directory can go
+            let _ = fs::remove_dir(staging_dir.parent().unwrap());
+        }
+
+        for deletion in deletions {
+            self.record_deletion(Some(segment.cam_idx), deletion);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes segments oldest first, across all cameras, until there are `min_free_bytes`
+    /// available. Segments ending after `prune_before` are left alone, returns whether enough
+    /// space was freed.
+    pub fn prune_oldest_segments(
+        &mut self,
+        config: &Config,
+        min_free_bytes: u64,
+        prune_before: DateTime<Local>,
+    ) -> Result<bool, Box<dyn StdError>> {
+        // Going by what's been freed rather than checking again works for dry runs as well
+        let available_bytes = fs2::available_space(&config.recordings_dir)?;
+
+        if available_bytes >= min_free_bytes {
+            return Ok(true);
+        }
+
+        let mut segments = segment_roots(config)
+            .iter()
+            .flat_map(|root| list_all_segments(root))
+            .filter(|s| s.end() <= prune_before)
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|s| s.start);
+
+        // Failing to load the locks must not lead to locked footage being deleted
+        let locks = load_locks()?;
+
+        for segment in segments {
+            if available_bytes + self.freed_bytes() >= min_free_bytes {
+                break;
+            }
+
+            if is_locked(&locks, &segment) {
+                continue;
+            }
+
+            self.delete_segment(&segment, CleanRule::LowDiskSpace)?;
+        }
+
+        Ok(available_bytes + self.freed_bytes() >= min_free_bytes)
+    }
+
+    /// Deletes what's left over besides finalized segments, frames of segments which failed to
+    /// encode and sidecars of segments which are gone
+    fn clean_cam_dir_leftovers(
+        &mut self,
+        cam_idx: i32,
+        cam_dir: &Path,
+        cutoff: DateTime<Local>,
+        locks: &[Lock],
+    ) -> Result<(), Box<dyn StdError>> {
+        // Anything in here was already on its way out when deleting it failed
+        if !self.dry_run && deletion_staging_dir(cam_dir).exists() {
+            let _lock = DELETION_STAGING_LOCK.lock().unwrap();
+            fs::remove_dir_all(deletion_staging_dir(cam_dir))?;
+        }
+
+        for dir_entry in fs::read_dir(cam_dir)? {
+            let path = dir_entry?.path();
+
+            let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let reencode_tmp = file_name.ends_with(&format!(".{}", REENCODE_TMP_EXTENSION));
+
+            // Leftovers are named after the segment they belong to, e.g.
+            // `rec-18.10.2026-14.03.59.250` for frames or `rec-18.10.2026-14.03.59.250.json` for
+            // sidecars
+            let Some((name, start)) = parse_segment_name_prefix(file_name)
+                .filter(|(_, start)| *start < cutoff || reencode_tmp)
+            else {
+                continue;
+            };
+
+            if reencode_tmp {
+                // Archiving runs earlier in the same pass, so this one's encode was interrupted
+                self.delete(Some(cam_idx), &path, start, CleanRule::UnfinishedReencode)?;
+            } else if path.is_dir() {
+                // Without a sidecar the frames count as a segment as long as possible
+                let frames_segment = Segment {
+                    cam_idx,
+                    path: path.clone(),
+                    start,
+                    meta: None,
+                };
+
+                if !is_locked(locks, &frames_segment) {
+                    self.delete(Some(cam_idx), &path, start, CleanRule::UnencodedFrames)?;
                 }
+            } else if path.extension().is_none_or(|ext| ext != "mp4")
+                && !cam_dir.join(format!("{}.mp4", name)).exists()
+            {
+                self.delete(Some(cam_idx), &path, start, CleanRule::OrphanedSidecar)?;
             }
         }
+
+        Ok(())
     }
 
-    Ok(())
+    fn clean_old_segments(&mut self, config: &Config) -> Result<(), Box<dyn StdError>> {
+        let now = Local::now();
+        let cutoff = now - chrono::Duration::hours(config.cleaner.retention_hours as i64);
+        let locks = load_locks()?;
+
+        for root in segment_roots(config) {
+            for (cam_idx, cam_dir) in list_cam_dirs(&root) {
+                for segment in list_segments(&root, cam_idx) {
+                    let retention_hours =
+                        segment_retention_hours(config, &segment, is_locked(&locks, &segment));
+
+                    let expired = retention_hours.is_some_and(|hours| {
+                        segment.end() < now - chrono::Duration::hours(hours as i64)
+                    });
+
+                    if expired {
+                        self.delete_segment(&segment, CleanRule::Retention)?;
+                    }
+                }
+
+                self.clean_cam_dir_leftovers(cam_idx, &cam_dir, cutoff, &locks)?;
+
+                // Only succeeds if the directory is empty, which is all we want
+                if !self.dry_run {
+                    let _ = fs::remove_dir(&cam_dir);
+                }
+            }
+        }
+
+        let (locked_count, locked_bytes) = locked_usage(config, &locks);
+        if locked_count > 0 {
+            println!(
+                "{} locked segments are holding {} MB",
+                locked_count,
+                locked_bytes / (1024 * 1024)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Applies the latest archive tier each segment is old enough for, unless it already was.
+    /// Locked footage is kept as it is.
+    fn archive_old_segments(&mut self, config: &Config) -> Result<(), Box<dyn StdError>> {
+        let tiers = &config.cleaner.archive_tiers;
+        if tiers.is_empty() {
+            return Ok(());
+        }
+
+        let locks = load_locks()?;
+        let now = Local::now();
+
+        for root in segment_roots(config) {
+            for segment in list_all_segments(&root) {
+                let age_hours = (now - segment.end()).num_hours();
+
+                let Some(tier_idx) = tiers
+                    .iter()
+                    .rposition(|t| age_hours >= t.after_hours as i64)
+                else {
+                    continue;
+                };
+
+                let already_archived = segment
+                    .meta
+                    .as_ref()
+                    .and_then(|m| m.archive_tier)
+                    .is_some_and(|t| t >= tier_idx);
+
+                if already_archived || is_locked(&locks, &segment) {
+                    continue;
+                }
+
+                if self.dry_run {
+                    println!(
+                        "Cleaner dry run would archive {:?} with tier {}",
+                        segment.path,
+                        tier_idx + 1
+                    );
+                    continue;
+                }
+
+                // One segment failing to archive shouldn't hold up the others
+                if let Err(error) = archive_segment(&segment, tier_idx, &tiers[tier_idx]) {
+                    println!(
+                        "Failed to archive segment {:?} due to error: {error}",
+                        segment.path
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only interval snapshots expire, the ones taken by hand are kept until removed by hand
+    fn clean_old_interval_snapshots(&mut self, config: &Config) -> Result<(), Box<dyn StdError>> {
+        let cutoff = Local::now()
+            - chrono::Duration::hours(config.snapshots.interval_retention_hours as i64);
+
+        for vdc in &config.video_devices {
+            let Ok(dir_entries) = fs::read_dir(cam_interval_snapshots_dir(config, vdc.idx)) else {
+                continue;
+            };
+
+            for dir_entry in dir_entries {
+                let path = dir_entry?.path();
+
+                let taken_at = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(parse_snapshot_time)
+                    .filter(|t| *t < cutoff);
+
+                if let Some(taken_at) = taken_at {
+                    self.delete(Some(vdc.idx), &path, taken_at, CleanRule::SnapshotRetention)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies every retention rule once
+    pub fn clean(&mut self, config: &Config) {
+        if let Err(error) = self.archive_old_segments(config) {
+            println!("Failed to archive old segments due to error: {error}");
+        }
+
+        if let Err(error) = self.clean_old_segments(config) {
+            println!("Failed to clean old segments due to error: {error}");
+        }
+
+        if let Err(error) = self.clean_old_interval_snapshots(config) {
+            println!("Failed to clean old snapshots due to error: {error}");
+        }
+    }
 }
 
 pub fn clean_old_files(config: Config) {
     loop {
-        let res = clean_files_older_than(
-            &config.recordings_dir,
-            Duration::from_secs(60 * 60 * 24 * 2),
-        ); // 2 days
-        if let Err(error) = res {
-            println!("Failed to clean old files due to error: {error}");
+        let mut cleaner = Cleaner::new(config.cleaner.dry_run);
+        cleaner.clean(&config);
+
+        if config.cleaner.dry_run {
+            for deletion in cleaner.deletions() {
+                println!(
+                    "Cleaner dry run would delete {:?} ({})",
+                    deletion.path,
+                    deletion.rule.label()
+                );
+            }
+
+            println!(
+                "Cleaner dry run would free {} MB",
+                cleaner.freed_bytes() / (1024 * 1024)
+            );
         }
 
-        thread::sleep(Duration::from_secs(60 * 60 * 6)); // 6 hours
+        thread::sleep(CLEAN_INTERVAL);
     }
 }
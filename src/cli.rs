@@ -0,0 +1,287 @@
+use std::{error::Error as StdError, path::PathBuf};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use clap::{Parser, Subcommand};
+
+use crate::{
+    cleaner::Cleaner,
+    config::Config,
+    evidence::{current_user, export_evidence, manifest_hash, verify_evidence},
+    export::{export_clips, exportable_cam_idxs},
+    locks::{add_lock, load_locks, locked_usage, remove_lock},
+    timelapse::{generate_timelapse, TimelapseRequest},
+};
+
+/// Without a subcommand the app window is opened
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Turns a camera's recorded footage into a timelapse video
+    Timelapse {
+        /// Index of the camera
+        #[arg(long)]
+        cam: i32,
+        /// Start of the footage, e.g. "2026-10-18" or "2026-10-18 08:00"
+        #[arg(long, value_parser = parse_cli_time)]
+        from: DateTime<Local>,
+        /// End of the footage, e.g. "2026-10-19" or "2026-10-18 20:00"
+        #[arg(long, value_parser = parse_cli_time)]
+        to: DateTime<Local>,
+        /// Seconds of footage between two frames of the timelapse
+        #[arg(long, default_value_t = 10)]
+        every: u64,
+        /// Frame rate of the timelapse video
+        #[arg(long, default_value_t = 30)]
+        fps: usize,
+    },
+    /// Exports a time range of footage as one trimmed clip per camera
+    Export {
+        /// Index of a camera, can be given more than once. Every camera with footage is exported
+        /// if left out
+        #[arg(long)]
+        cam: Vec<i32>,
+        /// Start of the clip, e.g. "2026-10-18 15:58"
+        #[arg(long, value_parser = parse_cli_time)]
+        from: DateTime<Local>,
+        /// End of the clip, e.g. "2026-10-18 16:07"
+        #[arg(long, value_parser = parse_cli_time)]
+        to: DateTime<Local>,
+        /// Export a package with a manifest and hashes of every file, which `verify` can check
+        #[arg(long)]
+        evidence: bool,
+        /// Who is exporting, recorded in the evidence manifest. Defaults to the logged in user
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// Checks that an evidence package is unaltered
+    Verify {
+        /// Directory of the package
+        package: PathBuf,
+        /// SHA-256 of the manifest as printed on export. Without it, a package whose manifest
+        /// was rewritten along with its files still verifies
+        #[arg(long)]
+        manifest_hash: Option<String>,
+    },
+    /// Protects footage in a time range from being deleted by the cleaner
+    Lock {
+        /// Index of the camera, every camera is locked if left out
+        #[arg(long)]
+        cam: Option<i32>,
+        /// Start of the locked footage, e.g. "2026-10-18" or "2026-10-18 08:00"
+        #[arg(long, value_parser = parse_cli_time)]
+        from: DateTime<Local>,
+        /// End of the locked footage, e.g. "2026-10-19" or "2026-10-18 20:00"
+        #[arg(long, value_parser = parse_cli_time)]
+        to: DateTime<Local>,
+        #[arg(long)]
+        reason: Option<String>,
+        /// The lock stops applying after this, it's kept until removed if left out
+        #[arg(long, value_parser = parse_cli_time)]
+        expires: Option<DateTime<Local>>,
+    },
+    /// Removes a lock, the footage is cleaned up as usual afterwards
+    Unlock {
+        /// Id of the lock, as listed by `locks`
+        id: u32,
+    },
+    /// Lists locks and how much space locked footage takes up
+    Locks,
+    /// Runs the cleaner once
+    Clean {
+        /// Only list what would be deleted and how much space it would free up
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+const CLI_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Accepts a date, which means its midnight, or a date with a time of day in local time
+pub fn parse_cli_time(s: &str) -> Result<DateTime<Local>, String> {
+    let naive = [CLI_TIME_FORMAT, "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .ok_or(format!(
+            "Invalid time {:?}, expected e.g. \"2026-10-18 08:00\"",
+            s
+        ))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or(format!("Time {:?} doesn't exist in the local time zone", s))
+}
+
+pub fn run_cli_command(config: &Config, command: CliCommand) -> Result<(), Box<dyn StdError>> {
+    match command {
+        CliCommand::Timelapse {
+            cam,
+            from,
+            to,
+            every,
+            fps,
+        } => {
+            let path = generate_timelapse(
+                config,
+                &TimelapseRequest {
+                    cam_idx: cam,
+                    from,
+                    to,
+                    sample_interval_secs: every,
+                    frame_rate: fps,
+                },
+            )?;
+
+            println!("Saved timelapse to {:?}", path);
+        }
+        CliCommand::Export {
+            cam,
+            from,
+            to,
+            evidence,
+            by,
+        } => {
+            let cam_idxs = match cam.is_empty() {
+                true => exportable_cam_idxs(config),
+                false => cam,
+            };
+
+            if evidence {
+                let exported_by = by.unwrap_or_else(current_user);
+                let path = export_evidence(config, &cam_idxs, from, to, &exported_by)?;
+
+                println!("Saved evidence package to {:?}", path);
+                println!("Manifest SHA-256: {}", manifest_hash(&path)?);
+                println!(
+                    "The package's hashes aren't signed, keep the manifest hash somewhere else and \
+                     pass it to `verify --manifest-hash` to rule out tampering"
+                );
+
+                return Ok(());
+            }
+
+            let results = export_clips(config, &cam_idxs, from, to);
+
+            for (cam_idx, result) in &results {
+                match result {
+                    Ok(path) => println!("Camera {}: {:?}", cam_idx, path),
+                    Err(error) => println!("Camera {}: failed to export: {}", cam_idx, error),
+                }
+            }
+
+            if results.iter().any(|(_, result)| result.is_err()) {
+                return Err(anyhow!("Not every clip could be exported").into());
+            }
+        }
+        CliCommand::Verify {
+            package,
+            manifest_hash,
+        } => {
+            let problems = verify_evidence(&package, manifest_hash.as_deref())?;
+
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+
+                return Err(anyhow!("Evidence package {:?} failed verification", package).into());
+            }
+
+            match manifest_hash {
+                Some(_) => println!("Evidence package {:?} is unaltered", package),
+                None => println!(
+                    "Evidence package {:?} matches its manifest, without --manifest-hash a \
+                     rewritten manifest can't be ruled out",
+                    package
+                ),
+            }
+        }
+        CliCommand::Lock {
+            cam,
+            from,
+            to,
+            reason,
+            expires,
+        } => {
+            let lock = add_lock(cam, from, to, reason, expires)?;
+
+            println!("Added lock {}", lock.id);
+        }
+        CliCommand::Unlock { id } => {
+            remove_lock(id)?;
+
+            println!("Removed lock {}", id);
+        }
+        CliCommand::Locks => {
+            let locks = load_locks()?;
+            let now = Local::now();
+
+            for lock in &locks {
+                println!(
+                    "{}\t{}\t{} - {}\t{}{}",
+                    lock.id,
+                    lock.cam_idx
+                        .map(|idx| format!("Camera {}", idx))
+                        .unwrap_or("All cameras".to_string()),
+                    lock.from.format(CLI_TIME_FORMAT),
+                    lock.to.format(CLI_TIME_FORMAT),
+                    lock.reason.as_deref().unwrap_or(""),
+                    match lock.expires_at {
+                        Some(expires_at) if !lock.is_active(now) => {
+                            format!(" (expired {})", expires_at.format(CLI_TIME_FORMAT))
+                        }
+                        Some(expires_at) => {
+                            format!(" (expires {})", expires_at.format(CLI_TIME_FORMAT))
+                        }
+                        None => String::new(),
+                    }
+                );
+            }
+
+            let (locked_count, locked_bytes) = locked_usage(config, &locks);
+            println!(
+                "{} locked segments, {} MB",
+                locked_count,
+                locked_bytes / (1024 * 1024)
+            );
+        }
+        CliCommand::Clean { dry_run } => {
+            let mut cleaner = Cleaner::new(dry_run);
+            cleaner.clean(config);
+
+            for deletion in cleaner.deletions() {
+                println!(
+                    "{}\t{} KB\t{}h old\t{}",
+                    deletion.path.to_string_lossy(),
+                    deletion.size_bytes / 1024,
+                    deletion.age_secs / (60 * 60),
+                    deletion.rule.label()
+                );
+            }
+
+            println!(
+                "{} {} items, {} MB",
+                match dry_run {
+                    true => "Would delete",
+                    false => "Deleted",
+                },
+                cleaner.deletions().len(),
+                cleaner.freed_bytes() / (1024 * 1024)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,14 @@
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::Write,
     path::{Path, PathBuf},
     sync::LazyLock,
 };
 
+use chrono::{NaiveDate, NaiveTime, Weekday};
 use home::home_dir;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 pub static CONFIG_PATH: LazyLock<Box<Path>> = LazyLock::new(|| {
@@ -28,29 +31,612 @@ static DEFAULT_RECORDINGS_PATH: LazyLock<Box<Path>> = LazyLock::new(|| {
     path.into_boxed_path()
 });
 
+/// What the recording worker should do when its frame queue is full
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueFullPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    Block,
+}
+
+fn video_device_recording_config_max_queued_frames_default() -> usize {
+    150
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    #[default]
+    Continuous,
+    Motion,
+    Off,
+}
+
+/// Time of day in a schedule rule, either fixed (`"22:00"`) or relative to the sun
+/// (`"sunset"`, `"sunset-30min"`, `"sunrise+1h"`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ScheduleTime {
+    Fixed(NaiveTime),
+    /// Offset in minutes from sunrise
+    Sunrise(i64),
+    /// Offset in minutes from sunset
+    Sunset(i64),
+}
+
+fn parse_schedule_time_offset(offset: &str) -> Result<i64, String> {
+    if offset.is_empty() {
+        return Ok(0);
+    }
+
+    let (sign, offset) = if let Some(offset) = offset.strip_prefix('+') {
+        (1, offset)
+    } else if let Some(offset) = offset.strip_prefix('-') {
+        (-1, offset)
+    } else {
+        return Err(format!("Expected + or - before offset {:?}", offset));
+    };
+
+    let (amount, multiplier) = if let Some(amount) = offset.strip_suffix("min") {
+        (amount, 1)
+    } else if let Some(amount) = offset.strip_suffix('h') {
+        (amount, 60)
+    } else {
+        return Err(format!("Expected min or h after offset {:?}", offset));
+    };
+
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid offset {:?}", offset))?;
+
+    Ok(sign * amount * multiplier)
+}
+
+impl TryFrom<String> for ScheduleTime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Some(offset) = value.strip_prefix("sunrise") {
+            Ok(ScheduleTime::Sunrise(parse_schedule_time_offset(offset)?))
+        } else if let Some(offset) = value.strip_prefix("sunset") {
+            Ok(ScheduleTime::Sunset(parse_schedule_time_offset(offset)?))
+        } else {
+            value
+                .parse::<NaiveTime>()
+                .map(ScheduleTime::Fixed)
+                .map_err(|_| format!("Invalid schedule time {:?}", value))
+        }
+    }
+}
+
+impl From<ScheduleTime> for String {
+    fn from(value: ScheduleTime) -> Self {
+        let (name, offset) = match value {
+            ScheduleTime::Fixed(time) => return time.format("%H:%M:%S").to_string(),
+            ScheduleTime::Sunrise(offset) => ("sunrise", offset),
+            ScheduleTime::Sunset(offset) => ("sunset", offset),
+        };
+
+        match offset {
+            0 => name.to_string(),
+            _ => format!("{}{:+}min", name, offset),
+        }
+    }
+}
+
+/// Recording mode for a time range on the given weekdays. If `end` is before `start` the range
+/// runs overnight into the next day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub days: Vec<Weekday>,
+    pub start: ScheduleTime,
+    pub end: ScheduleTime,
+    pub mode: RecordingMode,
+}
+
+/// Recording mode for a whole day, takes priority over the weekly rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleException {
+    pub date: NaiveDate,
+    pub mode: RecordingMode,
+}
+
+/// Weekly schedule, outside of its rules the recording config's `mode` is used
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RecordingSchedule {
+    #[serde(default)]
+    pub rules: Vec<ScheduleRule>,
+    #[serde(default)]
+    pub exceptions: Vec<ScheduleException>,
+}
+
+fn motion_config_threshold_default() -> f32 {
+    1.0
+}
+
+fn motion_config_pre_roll_secs_default() -> f32 {
+    5.0
+}
+
+fn motion_config_post_roll_secs_default() -> f32 {
+    10.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MotionConfig {
+    /// Percentage of the frame which has to change between two frames to count as motion
+    #[serde(default = "motion_config_threshold_default")]
+    pub threshold: f32,
+    /// Seconds of footage from before the motion started to include in the recording
+    #[serde(default = "motion_config_pre_roll_secs_default")]
+    pub pre_roll_secs: f32,
+    /// Seconds to keep recording for after the motion stopped
+    #[serde(default = "motion_config_post_roll_secs_default")]
+    pub post_roll_secs: f32,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            threshold: motion_config_threshold_default(),
+            pre_roll_secs: motion_config_pre_roll_secs_default(),
+            post_roll_secs: motion_config_post_roll_secs_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoDeviceRecordingConfig {
     pub enabled: bool,
+    #[serde(default)]
+    pub mode: RecordingMode,
+    #[serde(default)]
+    pub motion: MotionConfig,
+    #[serde(default)]
+    pub schedule: Option<RecordingSchedule>,
+    #[serde(default = "video_device_recording_config_max_queued_frames_default")]
+    pub max_queued_frames: usize,
+    #[serde(default)]
+    pub queue_full_policy: QueueFullPolicy,
+    /// Record at most this many frames per second, the preview still gets every frame
+    #[serde(default)]
+    pub record_fps: Option<usize>,
+}
+
+impl Default for VideoDeviceRecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: RecordingMode::default(),
+            motion: MotionConfig::default(),
+            schedule: None,
+            max_queued_frames: video_device_recording_config_max_queued_frames_default(),
+            queue_full_policy: QueueFullPolicy::default(),
+            record_fps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MotionZoneKind {
+    /// Motion only counts when it happens inside of include zones
+    #[default]
+    Include,
+    /// Motion inside of ignore zones is left out, unless it's above the zone's own sensitivity
+    Ignore,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct MotionZone {
+    #[serde(default)]
+    pub kind: MotionZoneKind,
+    /// Polygon points relative to the frame size, from (0.0, 0.0) to (1.0, 1.0)
+    pub points: Vec<(f32, f32)>,
+    /// Percentage of the zone which has to change to count as motion, for include zones this
+    /// falls back to the camera's motion threshold
+    #[serde(default)]
+    pub sensitivity: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyMaskStyle {
+    #[default]
+    Fill,
+    Pixelate,
+}
+
+/// Region which is blacked out or pixelated on every frame before it's used for anything
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PrivacyMask {
+    /// Polygon points relative to the frame size, from (0.0, 0.0) to (1.0, 1.0)
+    pub points: Vec<(f32, f32)>,
+    #[serde(default)]
+    pub style: PrivacyMaskStyle,
+}
+
+/// Clockwise rotation in degrees, one of 0, 90, 180 or 270
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum FrameRotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl TryFrom<u16> for FrameRotation {
+    type Error = String;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FrameRotation::None),
+            90 => Ok(FrameRotation::Clockwise90),
+            180 => Ok(FrameRotation::Clockwise180),
+            270 => Ok(FrameRotation::Clockwise270),
+            _ => Err(format!(
+                "Invalid rotation {}, expected 0, 90, 180 or 270",
+                value
+            )),
+        }
+    }
+}
+
+impl From<FrameRotation> for u16 {
+    fn from(value: FrameRotation) -> Self {
+        match value {
+            FrameRotation::None => 0,
+            FrameRotation::Clockwise90 => 90,
+            FrameRotation::Clockwise180 => 180,
+            FrameRotation::Clockwise270 => 270,
+        }
+    }
+}
+
+/// Rectangle relative to the rotated and flipped frame, from (0.0, 0.0) to (1.0, 1.0)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Applied to every frame right after capture, in the order rotation, flip, crop
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct FrameTransform {
+    #[serde(default)]
+    pub rotation: FrameRotation,
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    #[serde(default)]
+    pub crop: Option<CropRect>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+fn overlay_config_recording_default() -> bool {
+    true
+}
+
+fn overlay_config_time_format_default() -> String {
+    "%Y-%m-%d %H:%M:%S".to_string()
+}
+
+fn overlay_config_show_camera_name_default() -> bool {
+    true
+}
+
+fn overlay_config_font_scale_default() -> f64 {
+    0.6
+}
+
+fn overlay_config_background_default() -> bool {
+    true
+}
+
+/// Timestamp and camera name burned into frames
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverlayConfig {
+    /// Draw the overlay onto recorded footage
+    #[serde(default = "overlay_config_recording_default")]
+    pub recording: bool,
+    /// Draw the overlay onto the live preview
+    #[serde(default)]
+    pub preview: bool,
+    /// chrono format string, see https://docs.rs/chrono/latest/chrono/format/strftime
+    #[serde(default = "overlay_config_time_format_default")]
+    pub time_format: String,
+    #[serde(default = "overlay_config_show_camera_name_default")]
+    pub show_camera_name: bool,
+    #[serde(default)]
+    pub position: OverlayPosition,
+    #[serde(default = "overlay_config_font_scale_default")]
+    pub font_scale: f64,
+    /// Draw a black box behind the text so it stays readable
+    #[serde(default = "overlay_config_background_default")]
+    pub background: bool,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            recording: overlay_config_recording_default(),
+            preview: false,
+            time_format: overlay_config_time_format_default(),
+            show_camera_name: overlay_config_show_camera_name_default(),
+            position: OverlayPosition::default(),
+            font_scale: overlay_config_font_scale_default(),
+            background: overlay_config_background_default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct VideoDeviceConfig {
     pub idx: i32,
     #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
     pub recording: VideoDeviceRecordingConfig,
+    /// Deprecated, sets both `preview_max_width` and `recording_max_width`
     #[serde(default)]
     pub max_resolution_width: Option<u16>,
+    /// Frames shown in the grid are scaled down to at most this width
+    #[serde(default)]
+    pub preview_max_width: Option<u16>,
+    /// Recorded frames are scaled down to at most this width
+    #[serde(default)]
+    pub recording_max_width: Option<u16>,
+    #[serde(default)]
+    pub transform: FrameTransform,
+    #[serde(default)]
+    pub motion_zones: Vec<MotionZone>,
+    #[serde(default)]
+    pub privacy_masks: Vec<PrivacyMask>,
+    #[serde(default)]
+    pub overlay: OverlayConfig,
+    /// Take a snapshot every this many seconds
+    #[serde(default)]
+    pub snapshot_interval_secs: Option<u64>,
+}
+
+impl VideoDeviceConfig {
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("Camera {}", self.idx))
+    }
+
+    pub fn preview_max_width(&self) -> Option<u16> {
+        self.preview_max_width.or(self.max_resolution_width)
+    }
+
+    pub fn recording_max_width(&self) -> Option<u16> {
+        self.recording_max_width.or(self.max_resolution_width)
+    }
 }
 
 fn config_video_device_configs_default() -> Vec<VideoDeviceConfig> {
     vec![VideoDeviceConfig::default()]
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotFormat {
+    #[default]
+    Jpeg,
+    Png,
+}
+
+impl SnapshotFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Jpeg => "jpg",
+            SnapshotFormat::Png => "png",
+        }
+    }
+}
+
+fn snapshots_config_interval_retention_hours_default() -> u64 {
+    24 * 7
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotsConfig {
+    /// Defaults to a `snapshots` directory inside of the recordings directory
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub format: SnapshotFormat,
+    /// How long interval snapshots are kept for, snapshots taken by hand are kept forever
+    #[serde(default = "snapshots_config_interval_retention_hours_default")]
+    pub interval_retention_hours: u64,
+}
+
+impl Default for SnapshotsConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            format: SnapshotFormat::default(),
+            interval_retention_hours: snapshots_config_interval_retention_hours_default(),
+        }
+    }
+}
+
+fn cleaner_config_retention_hours_default() -> u64 {
+    24 * 2 // 2 days
+}
+
+/// What kind of footage a segment holds, used to pick how long it's kept
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentTag {
+    /// Recorded in continuous mode
+    Continuous,
+    /// Recorded because of motion
+    Motion,
+    /// Recorded while recording was turned on by hand from the UI
+    Manual,
+    /// Covered by a lock, this is never stored in a segment but worked out from the locks
+    Locked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ArchiveAction {
+    /// Re-encodes the segment in place, anything left out is kept as it is
+    Reencode {
+        #[serde(default)]
+        max_width: Option<u16>,
+        #[serde(default)]
+        fps: Option<usize>,
+        #[serde(default)]
+        bitrate_kbps: Option<u32>,
+    },
+    /// Moves the segment to a directory laid out like the recordings directory, it's still
+    /// cleaned up from there
+    Move { dir: PathBuf },
+}
+
+/// Applied to segments which ended at least `after_hours` ago, before they are deleted
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArchiveTier {
+    pub after_hours: u64,
+    #[serde(flatten)]
+    pub action: ArchiveAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanerConfig {
+    /// Segments are deleted once they ended this long ago
+    #[serde(default = "cleaner_config_retention_hours_default")]
+    pub retention_hours: u64,
+    /// Only log what would be deleted instead of deleting it
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Overrides `retention_hours` for segments with the given tags, a segment with several tags
+    /// is kept for the longest of them. Locked segments are kept forever unless `locked` is set.
+    #[serde(default)]
+    pub tag_retention_hours: BTreeMap<SegmentTag, u64>,
+    /// In order of `after_hours`, only the latest tier a segment is old enough for is applied
+    #[serde(default)]
+    pub archive_tiers: Vec<ArchiveTier>,
+}
+
+impl CleanerConfig {
+    /// Directories tiers move segments to, segments in these are cleaned up as well
+    pub fn archive_dirs(&self) -> Vec<PathBuf> {
+        self.archive_tiers
+            .iter()
+            .filter_map(|t| match &t.action {
+                ArchiveAction::Move { dir } => Some(dir.clone()),
+                ArchiveAction::Reencode { .. } => None,
+            })
+            .unique()
+            .collect()
+    }
+}
+
+impl Default for CleanerConfig {
+    fn default() -> Self {
+        Self {
+            retention_hours: cleaner_config_retention_hours_default(),
+            dry_run: false,
+            tag_retention_hours: BTreeMap::new(),
+            archive_tiers: Vec::new(),
+        }
+    }
+}
+
+fn disk_guard_config_soft_min_free_mb_default() -> u64 {
+    10 * 1024 // 10 GB
+}
+
+fn disk_guard_config_hard_min_free_mb_default() -> u64 {
+    2 * 1024 // 2 GB
+}
+
+fn disk_guard_config_check_interval_secs_default() -> u64 {
+    10
+}
+
+fn disk_guard_config_min_prune_age_hours_default() -> u64 {
+    24
+}
+
+/// Free space thresholds for the filesystem the recordings are on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskGuardConfig {
+    /// Below this the oldest segments are deleted early regardless of retention, as long as
+    /// they're older than `min_prune_age_hours`
+    #[serde(default = "disk_guard_config_soft_min_free_mb_default")]
+    pub soft_min_free_mb: u64,
+    /// Below this nothing gets written anymore until space frees up again
+    #[serde(default = "disk_guard_config_hard_min_free_mb_default")]
+    pub hard_min_free_mb: u64,
+    #[serde(default = "disk_guard_config_check_interval_secs_default")]
+    pub check_interval_secs: u64,
+    /// Footage newer than this is never pruned, an alert is raised instead once nothing older
+    /// is left
+    #[serde(default = "disk_guard_config_min_prune_age_hours_default")]
+    pub min_prune_age_hours: u64,
+}
+
+impl Default for DiskGuardConfig {
+    fn default() -> Self {
+        Self {
+            soft_min_free_mb: disk_guard_config_soft_min_free_mb_default(),
+            hard_min_free_mb: disk_guard_config_hard_min_free_mb_default(),
+            check_interval_secs: disk_guard_config_check_interval_secs_default(),
+            min_prune_age_hours: disk_guard_config_min_prune_age_hours_default(),
+        }
+    }
+}
+
+/// Used to work out sunrise and sunset for schedules, in degrees
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "config_video_device_configs_default")]
     pub video_devices: Vec<VideoDeviceConfig>,
     pub recordings_dir: PathBuf,
+    #[serde(default)]
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub snapshots: SnapshotsConfig,
+    #[serde(default)]
+    pub cleaner: CleanerConfig,
+    #[serde(default)]
+    pub disk_guard: DiskGuardConfig,
+}
+
+impl Config {
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.snapshots
+            .dir
+            .clone()
+            .unwrap_or_else(|| self.recordings_dir.join("snapshots"))
+    }
 }
 
 impl Default for Config {
@@ -58,6 +644,10 @@ impl Default for Config {
         Self {
             video_devices: config_video_device_configs_default(),
             recordings_dir: DEFAULT_RECORDINGS_PATH.to_path_buf(),
+            location: None,
+            snapshots: SnapshotsConfig::default(),
+            cleaner: CleanerConfig::default(),
+            disk_guard: DiskGuardConfig::default(),
         }
     }
 }
@@ -77,10 +667,76 @@ pub fn load_config() -> Config {
     }
 
     // Save always to write in defaults
+    save_config(&config);
+
+    config
+}
+
+pub fn save_config(config: &Config) {
     let mut file = File::create(&*CONFIG_PATH).unwrap();
-    file.write_all(serde_json::to_string_pretty(&config).unwrap().as_bytes())
+    file.write_all(serde_json::to_string_pretty(config).unwrap().as_bytes())
         .unwrap();
     file.flush().unwrap();
+}
 
-    config
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<ScheduleTime, String> {
+        ScheduleTime::try_from(value.to_string())
+    }
+
+    #[test]
+    fn fixed_times_parse() {
+        assert_eq!(
+            parse("22:00"),
+            Ok(ScheduleTime::Fixed(
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse("06:30:15"),
+            Ok(ScheduleTime::Fixed(
+                NaiveTime::from_hms_opt(6, 30, 15).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn sun_times_parse_with_offsets() {
+        assert_eq!(parse("sunrise"), Ok(ScheduleTime::Sunrise(0)));
+        assert_eq!(parse("sunset"), Ok(ScheduleTime::Sunset(0)));
+        assert_eq!(parse("sunset-30min"), Ok(ScheduleTime::Sunset(-30)));
+        assert_eq!(parse("sunrise+1h"), Ok(ScheduleTime::Sunrise(60)));
+        assert_eq!(parse("sunrise-2h"), Ok(ScheduleTime::Sunrise(-120)));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for value in [
+            "",
+            "25:00",
+            "noon",
+            "sunset30min",
+            "sunset-30",
+            "sunrise+1d",
+            "sunrise+onemin",
+            "sunset-",
+        ] {
+            assert!(parse(value).is_err(), "{:?} should not parse", value);
+        }
+    }
+
+    #[test]
+    fn times_serialize_back_to_what_parses() {
+        for time in [
+            ScheduleTime::Fixed(NaiveTime::from_hms_opt(22, 0, 0).unwrap()),
+            ScheduleTime::Sunrise(0),
+            ScheduleTime::Sunrise(60),
+            ScheduleTime::Sunset(-30),
+        ] {
+            assert_eq!(parse(&String::from(time)), Ok(time));
+        }
+    }
 }
@@ -0,0 +1,117 @@
+use std::{
+    error::Error as StdError,
+    sync::atomic::{self, AtomicBool, AtomicU64},
+    thread,
+    time::Duration,
+};
+
+use chrono::Local;
+
+use crate::{
+    cleaner::Cleaner,
+    config::Config,
+    events::{log_event, EventKind},
+};
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+static FREE_SPACE_MB: AtomicU64 = AtomicU64::new(u64::MAX);
+static SPACE_LOW: AtomicBool = AtomicBool::new(false);
+static WRITING_PAUSED: AtomicBool = AtomicBool::new(false);
+/// Set once pruning has run out of footage old enough to delete, so it's only alerted once
+static PRUNING_EXHAUSTED: AtomicBool = AtomicBool::new(false);
+
+/// Free space on the recordings filesystem as of the last check, `None` before the first one
+pub fn free_space_mb() -> Option<u64> {
+    Some(FREE_SPACE_MB.load(atomic::Ordering::Relaxed)).filter(|mb| *mb != u64::MAX)
+}
+
+/// Below the soft threshold, old footage is being pruned early
+pub fn space_low() -> bool {
+    SPACE_LOW.load(atomic::Ordering::Relaxed)
+}
+
+/// Below the hard threshold, nothing should be written to the recordings directory
+pub fn writing_paused() -> bool {
+    WRITING_PAUSED.load(atomic::Ordering::Relaxed)
+}
+
+fn check_free_space(config: &Config) -> Result<(), Box<dyn StdError>> {
+    let mut free_space_mb = fs2::available_space(&config.recordings_dir)? / BYTES_PER_MB;
+    let space_low = free_space_mb < config.disk_guard.soft_min_free_mb;
+
+    if space_low {
+        let prune_before =
+            Local::now() - chrono::Duration::hours(config.disk_guard.min_prune_age_hours as i64);
+        let mut cleaner = Cleaner::new(config.cleaner.dry_run);
+        let freed_enough = cleaner.prune_oldest_segments(
+            config,
+            config.disk_guard.soft_min_free_mb * BYTES_PER_MB,
+            prune_before,
+        )?;
+        free_space_mb = fs2::available_space(&config.recordings_dir)? / BYTES_PER_MB;
+
+        if !SPACE_LOW.swap(true, atomic::Ordering::Relaxed) {
+            let pruned = match config.cleaner.dry_run {
+                true => "nothing pruned in a dry run".to_string(),
+                false => format!(
+                    "pruned {} MB of the oldest footage",
+                    cleaner.freed_bytes() / BYTES_PER_MB
+                ),
+            };
+
+            log_event(
+                EventKind::DiskLow,
+                None,
+                format!("{} MB free, {}", free_space_mb, pruned),
+            );
+        }
+
+        if !freed_enough && !PRUNING_EXHAUSTED.swap(true, atomic::Ordering::Relaxed) {
+            log_event(
+                EventKind::DiskLow,
+                None,
+                format!(
+                    "{} MB free, no unlocked footage older than {} hours left to prune",
+                    free_space_mb, config.disk_guard.min_prune_age_hours
+                ),
+            );
+        } else if freed_enough {
+            PRUNING_EXHAUSTED.store(false, atomic::Ordering::Relaxed);
+        }
+    } else {
+        SPACE_LOW.store(false, atomic::Ordering::Relaxed);
+        PRUNING_EXHAUSTED.store(false, atomic::Ordering::Relaxed);
+    }
+
+    FREE_SPACE_MB.store(free_space_mb, atomic::Ordering::Relaxed);
+
+    let paused = free_space_mb < config.disk_guard.hard_min_free_mb;
+
+    if paused != WRITING_PAUSED.swap(paused, atomic::Ordering::Relaxed) {
+        match paused {
+            true => log_event(
+                EventKind::DiskLow,
+                None,
+                format!("{} MB free, recording paused", free_space_mb),
+            ),
+            false => println!("{} MB free, recording resumed", free_space_mb),
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps an eye on free space in the recordings directory, pruning and pausing recording as
+/// configured
+pub fn monitor_disk_space(config: Config) {
+    loop {
+        if let Err(error) = check_free_space(&config) {
+            println!("Failed to check free disk space due to error: {error}");
+        }
+
+        thread::sleep(Duration::from_secs(
+            config.disk_guard.check_interval_secs.max(1),
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    error::Error as StdError,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFIG_PATH;
+
+pub static EVENTS_PATH: LazyLock<Box<Path>> = LazyLock::new(|| {
+    CONFIG_PATH
+        .parent()
+        .unwrap()
+        .join("events.jsonl")
+        .into_boxed_path()
+});
+
+/// Serializes appends so events from different threads don't interleave
+static EVENTS_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    MotionStart,
+    MotionEnd,
+    CameraConnected,
+    CameraDisconnected,
+    SegmentFinalized,
+    EncodeFailure,
+    DiskLow,
+    CleanerDeletion,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 8] = [
+        EventKind::MotionStart,
+        EventKind::MotionEnd,
+        EventKind::CameraConnected,
+        EventKind::CameraDisconnected,
+        EventKind::SegmentFinalized,
+        EventKind::EncodeFailure,
+        EventKind::DiskLow,
+        EventKind::CleanerDeletion,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EventKind::MotionStart => "Motion Start",
+            EventKind::MotionEnd => "Motion End",
+            EventKind::CameraConnected => "Camera Connected",
+            EventKind::CameraDisconnected => "Camera Disconnected",
+            EventKind::SegmentFinalized => "Segment Finalized",
+            EventKind::EncodeFailure => "Encode Failure",
+            EventKind::DiskLow => "Disk Low",
+            EventKind::CleanerDeletion => "Cleaner Deletion",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub cam_idx: Option<i32>,
+    pub at: DateTime<Local>,
+    pub details: String,
+}
+
+fn append_event(event: &Event) -> Result<(), Box<dyn StdError>> {
+    let _lock = EVENTS_FILE_LOCK.lock().unwrap();
+
+    fs::create_dir_all(EVENTS_PATH.parent().unwrap())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&*EVENTS_PATH)?;
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+
+    Ok(())
+}
+
+/// Records an event to the persistent event log, failures are only printed so callers never
+/// have to care about the log being unavailable
+pub fn log_event(kind: EventKind, cam_idx: Option<i32>, details: impl Into<String>) {
+    let event = Event {
+        kind,
+        cam_idx,
+        at: Local::now(),
+        details: details.into(),
+    };
+
+    println!("Event: {:?}", event);
+
+    if let Err(error) = append_event(&event) {
+        println!("Failed to log event due to error: {error}");
+    }
+}
+
+/// Loads every event in the log, lines which fail to parse are skipped
+pub fn load_events() -> Result<Vec<Event>, Box<dyn StdError>> {
+    if !EVENTS_PATH.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(&*EVENTS_PATH)?;
+
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
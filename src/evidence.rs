@@ -0,0 +1,394 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error as StdError,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Config,
+    export::{clip_file_name, exports_dir, overlapping_segments, write_clip},
+    segments::SEGMENT_TIME_FORMAT,
+};
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Holds the manifest's own hash, in the format `sha256sum` reads. It isn't keyed, so anyone who
+/// can change the package can recompute it as well.
+const MANIFEST_HASH_FILE_NAME: &str = "manifest.json.sha256";
+
+/// A segment a clip was cut from, as it was at export time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceSegment {
+    pub path: PathBuf,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceClip {
+    pub cam_idx: i32,
+    pub cam_name: Option<String>,
+    /// File name of the clip within the package
+    pub file_name: String,
+    pub segments: Vec<EvidenceSegment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceManifest {
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub exported_at: DateTime<Local>,
+    pub exported_by: String,
+    pub clips: Vec<EvidenceClip>,
+    /// Cameras which were asked for but have no footage in the time range
+    #[serde(default)]
+    pub cams_without_footage: Vec<i32>,
+    /// SHA-256 of every file in the package besides the manifest, by file name
+    pub files: BTreeMap<String, String>,
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, Box<dyn StdError>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Name of the logged in user, recorded as the exporting user unless another one is given
+pub fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or("unknown".to_string())
+}
+
+/// SHA-256 of every file in the package besides the manifest, by file name
+fn hash_package_files(package_dir: &Path) -> Result<BTreeMap<String, String>, Box<dyn StdError>> {
+    let mut files = BTreeMap::new();
+
+    for dir_entry in fs::read_dir(package_dir)? {
+        let path = dir_entry?.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+        if file_name != MANIFEST_FILE_NAME && file_name != MANIFEST_HASH_FILE_NAME {
+            files.insert(file_name, sha256_file(&path)?);
+        }
+    }
+
+    Ok(files)
+}
+
+fn write_manifest(
+    package_dir: &Path,
+    manifest: &EvidenceManifest,
+) -> Result<(), Box<dyn StdError>> {
+    let manifest_path = package_dir.join(MANIFEST_FILE_NAME);
+    serde_json::to_writer_pretty(File::create(&manifest_path)?, manifest)?;
+
+    fs::write(
+        package_dir.join(MANIFEST_HASH_FILE_NAME),
+        format!("{}  {}\n", manifest_hash(package_dir)?, MANIFEST_FILE_NAME),
+    )?;
+
+    Ok(())
+}
+
+/// SHA-256 of the package's manifest, which covers every other file. Kept somewhere the package
+/// can't be changed from, it shows whether the manifest was rewritten along with the files.
+pub fn manifest_hash(package_dir: &Path) -> Result<String, Box<dyn StdError>> {
+    sha256_file(&package_dir.join(MANIFEST_FILE_NAME))
+}
+
+fn write_evidence_package(
+    app_config: &Config,
+    package_dir: &Path,
+    cam_idxs: &[i32],
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    exported_at: DateTime<Local>,
+    exported_by: &str,
+) -> Result<(), Box<dyn StdError>> {
+    let mut clips = Vec::new();
+    let mut cams_without_footage = Vec::new();
+
+    for cam_idx in cam_idxs {
+        let segments = overlapping_segments(app_config, *cam_idx, from, to);
+
+        if segments.is_empty() {
+            println!(
+                "No recorded footage for video device {} in the given time range",
+                cam_idx
+            );
+            cams_without_footage.push(*cam_idx);
+            continue;
+        }
+
+        // Hashed before cutting, so the manifest has what the clip was actually made from
+        let evidence_segments = segments
+            .iter()
+            .map(|segment| {
+                Ok(EvidenceSegment {
+                    path: segment.path.clone(),
+                    start: segment.start,
+                    end: segment.end(),
+                    sha256: sha256_file(&segment.path)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn StdError>>>()?;
+
+        let file_name = clip_file_name(*cam_idx, from, to);
+        write_clip(&segments, from, to, &package_dir.join(&file_name))?;
+
+        clips.push(EvidenceClip {
+            cam_idx: *cam_idx,
+            cam_name: app_config
+                .video_devices
+                .iter()
+                .find(|vdc| vdc.idx == *cam_idx)
+                .and_then(|vdc| vdc.name.clone()),
+            file_name,
+            segments: evidence_segments,
+        });
+    }
+
+    if clips.is_empty() {
+        return Err(
+            anyhow!("No recorded footage for any of the cameras in the given time range").into(),
+        );
+    }
+
+    let manifest = EvidenceManifest {
+        from,
+        to,
+        exported_at,
+        exported_by: exported_by.to_string(),
+        clips,
+        cams_without_footage,
+        files: hash_package_files(package_dir)?,
+    };
+
+    write_manifest(package_dir, &manifest)
+}
+
+/// Exports one clip per camera into a new package directory along with a manifest of where
+/// they came from and hashes of every file, returns the package's path. Cameras without footage
+/// are only noted in the manifest, it fails if none has any.
+pub fn export_evidence(
+    app_config: &Config,
+    cam_idxs: &[i32],
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    exported_by: &str,
+) -> Result<PathBuf, Box<dyn StdError>> {
+    if cam_idxs.is_empty() {
+        return Err(anyhow!("No cameras to export").into());
+    }
+
+    if from >= to {
+        return Err(anyhow!("Export start has to be before its end").into());
+    }
+
+    let exported_at = Local::now();
+    let package_dir = exports_dir(app_config).join(format!(
+        "evidence-{}",
+        exported_at.format(SEGMENT_TIME_FORMAT)
+    ));
+
+    if package_dir.exists() {
+        return Err(anyhow!("Evidence package {:?} already exists", package_dir).into());
+    }
+
+    fs::create_dir_all(&package_dir)?;
+
+    // A package missing some of its clips is worse than none at all
+    if let Err(error) = write_evidence_package(
+        app_config,
+        &package_dir,
+        cam_idxs,
+        from,
+        to,
+        exported_at,
+        exported_by,
+    ) {
+        let _ = fs::remove_dir_all(&package_dir);
+        return Err(error);
+    }
+
+    println!(
+        "Exported evidence package to {:?}, manifest SHA-256 {}",
+        package_dir,
+        manifest_hash(&package_dir)?
+    );
+
+    Ok(package_dir)
+}
+
+/// Re-checks every hash of a package, returns what doesn't match. Empty if the package is
+/// unaltered.
+///
+/// The hashes aren't keyed, on their own they only catch changes by someone who didn't also
+/// rewrite the manifest. Deliberate tampering is only ruled out by checking against
+/// `expected_manifest_hash`, taken from a copy of the manifest hash kept outside the package.
+pub fn verify_evidence(
+    package_dir: &Path,
+    expected_manifest_hash: Option<&str>,
+) -> Result<Vec<String>, Box<dyn StdError>> {
+    let mut problems = Vec::new();
+
+    let manifest_path = package_dir.join(MANIFEST_FILE_NAME);
+    let manifest_hash_file = fs::read_to_string(package_dir.join(MANIFEST_HASH_FILE_NAME))?;
+
+    let actual_manifest_hash = manifest_hash(package_dir)?;
+
+    if manifest_hash_file.split_whitespace().next() != Some(actual_manifest_hash.as_str()) {
+        problems.push(format!("{} has been changed", MANIFEST_FILE_NAME));
+    }
+
+    let matches_expected_hash =
+        expected_manifest_hash.is_none_or(|h| h.trim().eq_ignore_ascii_case(&actual_manifest_hash));
+
+    if !matches_expected_hash {
+        problems.push(format!(
+            "{} doesn't match the given hash",
+            MANIFEST_FILE_NAME
+        ));
+    }
+
+    let manifest: EvidenceManifest = serde_json::from_reader(File::open(&manifest_path)?)?;
+
+    for (file_name, sha256) in &manifest.files {
+        let path = package_dir.join(file_name);
+
+        if !path.exists() {
+            problems.push(format!("{} is missing", file_name));
+        } else if sha256_file(&path)? != *sha256 {
+            problems.push(format!("{} has been changed", file_name));
+        }
+    }
+
+    for dir_entry in fs::read_dir(package_dir)? {
+        let file_name = dir_entry?.file_name().to_string_lossy().to_string();
+
+        if file_name != MANIFEST_FILE_NAME
+            && file_name != MANIFEST_HASH_FILE_NAME
+            && !manifest.files.contains_key(&file_name)
+        {
+            problems.push(format!("{} isn't part of the package", file_name));
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    /// Package with two clips and a manifest covering them, in a fresh temporary directory
+    fn write_test_package(name: &str) -> PathBuf {
+        let package_dir = env::temp_dir().join(format!("evidence-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&package_dir);
+        fs::create_dir_all(&package_dir).unwrap();
+
+        fs::write(package_dir.join("clip-cam-0.mp4"), "first clip").unwrap();
+        fs::write(package_dir.join("clip-cam-1.mp4"), "second clip").unwrap();
+
+        rewrite_manifest(&package_dir);
+
+        package_dir
+    }
+
+    /// Hashes the package's files again and writes a new manifest for them
+    fn rewrite_manifest(package_dir: &Path) {
+        let now = Local::now();
+
+        let manifest = EvidenceManifest {
+            from: now,
+            to: now,
+            exported_at: now,
+            exported_by: "test".to_string(),
+            clips: Vec::new(),
+            cams_without_footage: Vec::new(),
+            files: hash_package_files(package_dir).unwrap(),
+        };
+
+        write_manifest(package_dir, &manifest).unwrap();
+    }
+
+    #[test]
+    fn unaltered_package_verifies() {
+        let package_dir = write_test_package("unaltered");
+
+        let problems = verify_evidence(&package_dir, None).unwrap();
+        fs::remove_dir_all(&package_dir).unwrap();
+
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn changed_file_is_reported() {
+        let package_dir = write_test_package("changed");
+        fs::write(package_dir.join("clip-cam-0.mp4"), "edited clip").unwrap();
+
+        let problems = verify_evidence(&package_dir, None).unwrap();
+        fs::remove_dir_all(&package_dir).unwrap();
+
+        assert_eq!(problems, vec!["clip-cam-0.mp4 has been changed"]);
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let package_dir = write_test_package("missing");
+        fs::remove_file(package_dir.join("clip-cam-1.mp4")).unwrap();
+
+        let problems = verify_evidence(&package_dir, None).unwrap();
+        fs::remove_dir_all(&package_dir).unwrap();
+
+        assert_eq!(problems, vec!["clip-cam-1.mp4 is missing"]);
+    }
+
+    #[test]
+    fn extra_file_is_reported() {
+        let package_dir = write_test_package("extra");
+        fs::write(package_dir.join("clip-cam-2.mp4"), "third clip").unwrap();
+
+        let problems = verify_evidence(&package_dir, None).unwrap();
+        fs::remove_dir_all(&package_dir).unwrap();
+
+        assert_eq!(problems, vec!["clip-cam-2.mp4 isn't part of the package"]);
+    }
+
+    #[test]
+    fn rewritten_manifest_is_only_caught_by_the_kept_hash() {
+        let package_dir = write_test_package("rewritten");
+        let kept_hash = manifest_hash(&package_dir).unwrap();
+
+        fs::write(package_dir.join("clip-cam-0.mp4"), "edited clip").unwrap();
+        rewrite_manifest(&package_dir);
+
+        let problems_without_hash = verify_evidence(&package_dir, None).unwrap();
+        let problems_with_hash = verify_evidence(&package_dir, Some(&kept_hash)).unwrap();
+        fs::remove_dir_all(&package_dir).unwrap();
+
+        assert!(
+            problems_without_hash.is_empty(),
+            "{:?}",
+            problems_without_hash
+        );
+        assert_eq!(
+            problems_with_hash,
+            vec!["manifest.json doesn't match the given hash"]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    error::Error as StdError,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+
+use crate::{
+    config::Config,
+    segments::{list_cam_dirs, list_cam_segments, segment_roots, Segment, SEGMENT_TIME_FORMAT},
+    utils::video::run_ffmpeg,
+};
+
+pub fn exports_dir(app_config: &Config) -> PathBuf {
+    app_config.recordings_dir.join("exports")
+}
+
+/// Cameras with footage in the recordings or any archive directory
+pub fn exportable_cam_idxs(app_config: &Config) -> Vec<i32> {
+    let mut cam_idxs = segment_roots(app_config)
+        .iter()
+        .flat_map(|root| list_cam_dirs(root))
+        .map(|(cam_idx, _)| cam_idx)
+        .collect::<Vec<_>>();
+    cam_idxs.sort();
+    cam_idxs.dedup();
+
+    cam_idxs
+}
+
+/// Quotes a path for an ffmpeg concat list
+fn concat_list_path(path: &Path) -> Result<String, Box<dyn StdError>> {
+    let path = fs::canonicalize(path)?;
+
+    Ok(format!(
+        "'{}'",
+        path.to_string_lossy().replace('\'', "'\\''")
+    ))
+}
+
+/// ffmpeg concat list playing the part of each segment which falls into the time range
+fn concat_list(
+    segments: &[Segment],
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<String, Box<dyn StdError>> {
+    let mut list = String::new();
+
+    for segment in segments {
+        list += &format!("file {}\n", concat_list_path(&segment.path)?);
+
+        if from > segment.start {
+            let inpoint = (from - segment.start).num_milliseconds() as f64 / 1000.0;
+            list += &format!("inpoint {:.3}\n", inpoint);
+        }
+
+        if to < segment.end() {
+            let outpoint = (to - segment.start).num_milliseconds() as f64 / 1000.0;
+            list += &format!("outpoint {:.3}\n", outpoint);
+        }
+    }
+
+    Ok(list)
+}
+
+/// The camera's segments overlapping the time range, from the recordings and every archive
+/// directory, sorted by their start time
+pub fn overlapping_segments(
+    app_config: &Config,
+    cam_idx: i32,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Vec<Segment> {
+    list_cam_segments(app_config, cam_idx)
+        .into_iter()
+        .filter(|s| s.start < to && s.end() > from)
+        .collect()
+}
+
+pub fn clip_segments(
+    app_config: &Config,
+    cam_idx: i32,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<Vec<Segment>, Box<dyn StdError>> {
+    if from >= to {
+        return Err(anyhow!("Export start has to be before its end").into());
+    }
+
+    let segments = overlapping_segments(app_config, cam_idx, from, to);
+
+    if segments.is_empty() {
+        return Err(anyhow!(
+            "No recorded footage for video device {} in the given time range",
+            cam_idx
+        )
+        .into());
+    }
+
+    Ok(segments)
+}
+
+pub fn clip_file_name(cam_idx: i32, from: DateTime<Local>, to: DateTime<Local>) -> String {
+    format!(
+        "clip-cam-{}-{}-{}.mp4",
+        cam_idx,
+        from.format(SEGMENT_TIME_FORMAT),
+        to.format(SEGMENT_TIME_FORMAT)
+    )
+}
+
+/// Stitches the segments into one mp4 at `clip_path`, trimmed to the time range. Gaps in the
+/// footage are left out of the clip.
+pub fn write_clip(
+    segments: &[Segment],
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    clip_path: &Path,
+) -> Result<(), Box<dyn StdError>> {
+    let list_path = clip_path.with_extension("txt");
+    fs::write(&list_path, concat_list(segments, from, to)?)?;
+
+    // Re-encoded rather than copied, so cuts don't have to fall on keyframes
+    let res = run_ffmpeg(
+        None,
+        &[
+            "-y",
+            "-f",
+            "concat",
+            "-safe",
+            "0",
+            "-i",
+            &list_path.to_string_lossy(),
+            "-c:v",
+            "libx264",
+            "-pix_fmt",
+            "yuv420p",
+            "-f",
+            "mp4",
+            &clip_path.to_string_lossy(),
+        ],
+    );
+
+    let _ = fs::remove_file(&list_path);
+
+    // Don't leave a partial clip behind which could pass for a complete one
+    if res.is_err() {
+        let _ = fs::remove_file(clip_path);
+    }
+
+    res
+}
+
+/// Exports the camera's footage between `from` and `to` as one clip, returns its path
+pub fn export_clip(
+    app_config: &Config,
+    cam_idx: i32,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<PathBuf, Box<dyn StdError>> {
+    let segments = clip_segments(app_config, cam_idx, from, to)?;
+
+    let exports_dir = exports_dir(app_config);
+    fs::create_dir_all(&exports_dir)?;
+
+    let clip_path = exports_dir.join(clip_file_name(cam_idx, from, to));
+    write_clip(&segments, from, to, &clip_path)?;
+
+    println!(
+        "Exported clip of video device {} to {:?}",
+        cam_idx, clip_path
+    );
+
+    Ok(clip_path)
+}
+
+/// Exports one clip per camera, failures of one camera don't stop the others
+pub fn export_clips(
+    app_config: &Config,
+    cam_idxs: &[i32],
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Vec<(i32, Result<PathBuf, String>)> {
+    cam_idxs
+        .iter()
+        .map(|cam_idx| {
+            (
+                *cam_idx,
+                export_clip(app_config, *cam_idx, from, to).map_err(|e| e.to_string()),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+use std::{
+    error::Error as StdError,
+    fs::{self, File, OpenOptions},
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Config, CONFIG_PATH},
+    segments::{list_all_segments, segment_roots, Segment},
+};
+
+pub static LOCKS_PATH: LazyLock<Box<Path>> = LazyLock::new(|| {
+    CONFIG_PATH
+        .parent()
+        .unwrap()
+        .join("locks.json")
+        .into_boxed_path()
+});
+
+/// Serializes changes to the locks file within this process
+static LOCKS_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Takes an exclusive lock on the locks file which is shared with other processes, e.g. the
+/// CLI, it's released once the returned file is dropped
+fn lock_locks_file() -> Result<File, Box<dyn StdError>> {
+    fs::create_dir_all(LOCKS_PATH.parent().unwrap())?;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(LOCKS_PATH.with_extension("json.lock"))?;
+    file.lock_exclusive()?;
+
+    Ok(file)
+}
+
+/// Footage in a lock's time range is never deleted by the cleaner while the lock is active
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lock {
+    pub id: u32,
+    /// `None` locks the time range on every camera
+    pub cam_idx: Option<i32>,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// The lock stops applying after this, `None` keeps it until it's removed
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Local>>,
+    pub created_at: DateTime<Local>,
+}
+
+impl Lock {
+    pub fn is_active(&self, at: DateTime<Local>) -> bool {
+        self.expires_at.is_none_or(|e| at < e)
+    }
+
+    pub fn covers(&self, segment: &Segment) -> bool {
+        self.cam_idx.is_none_or(|idx| idx == segment.cam_idx)
+            && self.from < segment.end()
+            && segment.start < self.to
+    }
+}
+
+/// Whether any active lock covers part of the segment
+pub fn is_locked(locks: &[Lock], segment: &Segment) -> bool {
+    let now = Local::now();
+
+    locks.iter().any(|l| l.is_active(now) && l.covers(segment))
+}
+
+pub fn load_locks() -> Result<Vec<Lock>, Box<dyn StdError>> {
+    if !LOCKS_PATH.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(&*LOCKS_PATH)?;
+
+    Ok(serde_json::from_reader(file)?)
+}
+
+/// Writes the locks next to the file and moves them over it, so readers never see it half
+/// written
+fn save_locks(locks: &[Lock]) -> Result<(), Box<dyn StdError>> {
+    fs::create_dir_all(LOCKS_PATH.parent().unwrap())?;
+
+    let tmp_path = LOCKS_PATH.with_extension("json.tmp");
+    serde_json::to_writer_pretty(File::create(&tmp_path)?, locks)?;
+    fs::rename(&tmp_path, &*LOCKS_PATH)?;
+
+    Ok(())
+}
+
+/// Adds a lock and returns it with its id filled in
+pub fn add_lock(
+    cam_idx: Option<i32>,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    reason: Option<String>,
+    expires_at: Option<DateTime<Local>>,
+) -> Result<Lock, Box<dyn StdError>> {
+    if from >= to {
+        return Err(anyhow!("Lock start has to be before its end").into());
+    }
+
+    let _lock = LOCKS_FILE_LOCK.lock().unwrap();
+    let _file_lock = lock_locks_file()?;
+
+    let mut locks = load_locks()?;
+
+    let lock = Lock {
+        id: locks.iter().map(|l| l.id + 1).max().unwrap_or(1),
+        cam_idx,
+        from,
+        to,
+        reason,
+        expires_at,
+        created_at: Local::now(),
+    };
+
+    locks.push(lock.clone());
+    save_locks(&locks)?;
+
+    Ok(lock)
+}
+
+pub fn lock_segment(segment: &Segment, reason: Option<String>) -> Result<Lock, Box<dyn StdError>> {
+    add_lock(
+        Some(segment.cam_idx),
+        segment.start,
+        segment.end(),
+        reason,
+        None,
+    )
+}
+
+pub fn remove_lock(id: u32) -> Result<(), Box<dyn StdError>> {
+    let _lock = LOCKS_FILE_LOCK.lock().unwrap();
+    let _file_lock = lock_locks_file()?;
+
+    let mut locks = load_locks()?;
+    let locks_len = locks.len();
+    locks.retain(|l| l.id != id);
+
+    if locks.len() == locks_len {
+        return Err(anyhow!("No lock with id {}", id).into());
+    }
+
+    save_locks(&locks)
+}
+
+/// Number of locked segments and their total size in bytes
+pub fn locked_usage(config: &Config, locks: &[Lock]) -> (usize, u64) {
+    segment_roots(config)
+        .iter()
+        .flat_map(|root| list_all_segments(root))
+        .filter(|s| is_locked(locks, s))
+        .fold((0, 0), |(count, bytes), s| {
+            (
+                count + 1,
+                bytes + fs::metadata(&s.path).map(|m| m.len()).unwrap_or(0),
+            )
+        })
+}
@@ -1,18 +1,49 @@
+use clap::Parser;
 use cleaner::clean_old_files;
+use cli::{run_cli_command, Cli};
 use config::{load_config, Config};
+use disk_guard::monitor_disk_space;
 use eframe::egui;
 use itertools::Itertools;
+use snapshots::take_interval_snapshots;
 use std::{
     collections::BTreeMap,
     error::Error as StdError,
     sync::{atomic, Arc},
     thread,
 };
-use ui::{cam_grid::show_cam_grid, top_menu_bar::show_top_menu_bar};
+use ui::{
+    cam_grid::show_cam_grid,
+    events_panel::{show_events_panel, EventsPanel},
+    export_window::{show_export_window, ExportWindow},
+    locks_window::{show_locks_window, LocksWindow},
+    playback_window::{show_playback_window, PlaybackWindow},
+    timelapse_window::{show_timelapse_window, TimelapseWindow},
+    top_menu_bar::show_top_menu_bar,
+    zone_editor::{show_zone_editor_window, ZoneEditor},
+};
 use video::{capture_video, VideoCam};
 
+mod archive;
 mod cleaner;
+mod cli;
 mod config;
+mod disk_guard;
+mod events;
+mod evidence;
+mod export;
+mod locks;
+mod motion;
+mod overlay;
+mod playback;
+mod privacy;
+mod recorder;
+mod schedule;
+mod segments;
+mod snapshots;
+mod thumbnails;
+mod timelapse;
+mod transform;
 mod ui;
 mod utils;
 mod video;
@@ -26,7 +57,13 @@ pub type CamsMapping = BTreeMap<
 >;
 
 fn main() -> Result<(), Box<dyn StdError>> {
+    let cli = Cli::parse();
     let config = load_config();
+
+    if let Some(command) = cli.command {
+        return run_cli_command(&config, command);
+    }
+
     println!("Config: {:#?}", config);
 
     let cams: Vec<Arc<VideoCam>> = config
@@ -41,12 +78,23 @@ fn main() -> Result<(), Box<dyn StdError>> {
         thread::spawn(|| clean_old_files(config));
     }
 
+    {
+        let config = config.clone();
+        thread::spawn(|| monitor_disk_space(config));
+    }
+
     let cams: CamsMapping = cams
         .into_iter()
         .map(|cam| {
             // .clone() solves all our problems :)
             let cam_idx = cam.config.idx;
 
+            if cam.config.snapshot_interval_secs.is_some() {
+                let cam = cam.clone();
+                let config = config.clone();
+                thread::spawn(move || take_interval_snapshots(config, cam));
+            }
+
             let thread_handle = {
                 let cam = cam.clone();
                 let config = config.clone();
@@ -84,10 +132,15 @@ fn main() -> Result<(), Box<dyn StdError>> {
 }
 
 struct SMApp {
-    #[allow(dead_code)]
     config: Config,
     cams: CamsMapping,
     cams_paused: bool,
+    zone_editor: Option<ZoneEditor>,
+    events_panel: Option<EventsPanel>,
+    locks_window: Option<LocksWindow>,
+    timelapse_window: Option<TimelapseWindow>,
+    playback_window: Option<PlaybackWindow>,
+    export_window: Option<ExportWindow>,
 }
 
 impl SMApp {
@@ -103,6 +156,12 @@ impl SMApp {
             config,
             cams,
             cams_paused: true,
+            zone_editor: None,
+            events_panel: None,
+            locks_window: None,
+            timelapse_window: None,
+            playback_window: None,
+            export_window: None,
         }
     }
 }
@@ -119,6 +178,13 @@ impl eframe::App for SMApp {
                 show_cam_grid(self, ctx, ui);
             });
 
+        show_zone_editor_window(self, ctx);
+        show_events_panel(self, ctx);
+        show_locks_window(self, ctx);
+        show_timelapse_window(self, ctx);
+        show_playback_window(self, ctx);
+        show_export_window(self, ctx);
+
         // Don't want to waste CPU unless we need the cams to be showing
         if !self.cams_paused {
             ctx.request_repaint();
@@ -0,0 +1,263 @@
+use opencv::{
+    core::{self, Mat, MatTraitConst, Point, Scalar, Size, Vector},
+    imgproc,
+};
+
+use crate::config::{MotionConfig, MotionZone, MotionZoneKind};
+
+/// Width frames are downscaled to before comparing them, motion doesn't need full resolution
+const ANALYSIS_WIDTH: i32 = 320;
+
+/// Per-pixel brightness difference below which a change is treated as noise
+const PIXEL_DIFF_THRESHOLD: f64 = 25.0;
+
+/// Zones and privacy masks with fewer points don't enclose any area and are left out
+pub const MIN_ZONE_POINTS: usize = 3;
+
+/// Area of the analysed frame which is checked for motion along with its threshold
+struct MotionArea {
+    mask: Mat,
+    mask_px: i32,
+    threshold: f32,
+}
+
+/// Motion areas built from a camera's zones, rebuilt whenever the zones or frame size change
+struct MotionAreas {
+    zones: Vec<MotionZone>,
+    size: Size,
+    areas: Vec<MotionArea>,
+}
+
+fn zone_mask(zone: &MotionZone, size: Size) -> opencv::Result<Mat> {
+    let mut mask =
+        Mat::new_rows_cols_with_default(size.height, size.width, core::CV_8UC1, Scalar::all(0.0))?;
+
+    let polygon: Vector<Point> = zone
+        .points
+        .iter()
+        .map(|(x, y)| {
+            Point::new(
+                (x * size.width as f32) as i32,
+                (y * size.height as f32) as i32,
+            )
+        })
+        .collect();
+
+    if polygon.len() >= MIN_ZONE_POINTS {
+        imgproc::fill_poly_def(
+            &mut mask,
+            &Vector::<Vector<Point>>::from_iter([polygon]),
+            Scalar::all(255.0),
+        )?;
+    }
+
+    Ok(mask)
+}
+
+fn build_motion_areas(
+    zones: &[MotionZone],
+    size: Size,
+    threshold: f32,
+) -> opencv::Result<Vec<MotionArea>> {
+    let mut ignored =
+        Mat::new_rows_cols_with_default(size.height, size.width, core::CV_8UC1, Scalar::all(0.0))?;
+    let mut areas = Vec::new();
+
+    // An unfinished include zone would otherwise stand in for the whole frame with an empty mask
+    let zones = zones
+        .iter()
+        .filter(|z| z.points.len() >= MIN_ZONE_POINTS)
+        .collect::<Vec<_>>();
+
+    for zone in zones.iter().filter(|z| z.kind == MotionZoneKind::Ignore) {
+        let mask = zone_mask(zone, size)?;
+
+        let mut ignored_ = Mat::default();
+        core::bitwise_or_def(&ignored, &mask, &mut ignored_)?;
+        ignored = ignored_;
+
+        // An ignore zone with its own sensitivity still counts large changes, e.g. a person
+        // walking past a tree rather than the leaves moving
+        if let Some(sensitivity) = zone.sensitivity {
+            areas.push((mask, sensitivity));
+        }
+    }
+
+    let mut not_ignored = Mat::default();
+    core::bitwise_not_def(&ignored, &mut not_ignored)?;
+
+    let include_zones = zones
+        .iter()
+        .filter(|z| z.kind == MotionZoneKind::Include)
+        .collect::<Vec<_>>();
+
+    if include_zones.is_empty() {
+        areas.push((not_ignored, threshold));
+    } else {
+        for zone in include_zones {
+            let mut mask = Mat::default();
+            core::bitwise_and_def(&zone_mask(zone, size)?, &not_ignored, &mut mask)?;
+            areas.push((mask, zone.sensitivity.unwrap_or(threshold)));
+        }
+    }
+
+    areas
+        .into_iter()
+        .map(|(mask, threshold)| {
+            Ok(MotionArea {
+                mask_px: core::count_non_zero(&mask)?,
+                mask,
+                threshold,
+            })
+        })
+        .collect()
+}
+
+/// Detects motion by differencing each frame against the previous one
+#[derive(Default)]
+pub struct MotionDetector {
+    prev_frame: Option<Mat>,
+    areas: Option<MotionAreas>,
+}
+
+impl MotionDetector {
+    /// Returns whether enough of any motion area changed since the last frame
+    pub fn update(
+        &mut self,
+        frame: &Mat,
+        zones: &[MotionZone],
+        threshold: f32,
+    ) -> opencv::Result<bool> {
+        let frame_size = frame.size()?;
+        let analysis_height =
+            ((ANALYSIS_WIDTH as f32 / frame_size.width as f32) * frame_size.height as f32) as i32;
+
+        let mut small = Mat::default();
+        imgproc::resize(
+            frame,
+            &mut small,
+            Size {
+                width: ANALYSIS_WIDTH,
+                height: analysis_height.max(1),
+            },
+            0.0,
+            0.0,
+            imgproc::InterpolationFlags::INTER_AREA as i32,
+        )?;
+
+        let mut gray = Mat::default();
+        imgproc::cvt_color(&small, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur_def(&gray, &mut blurred, Size::new(5, 5), 0.0)?;
+
+        let analysis_size = blurred.size()?;
+
+        let areas_stale = self
+            .areas
+            .as_ref()
+            .is_none_or(|a| a.size != analysis_size || a.zones != zones);
+
+        if areas_stale {
+            self.areas = Some(MotionAreas {
+                zones: zones.to_vec(),
+                size: analysis_size,
+                areas: build_motion_areas(zones, analysis_size, threshold)?,
+            });
+        }
+
+        let mut motion_detected = false;
+
+        if let Some(prev_frame) = &self.prev_frame {
+            if prev_frame.size()? == analysis_size {
+                let mut diff = Mat::default();
+                core::absdiff(prev_frame, &blurred, &mut diff)?;
+
+                let mut changed = Mat::default();
+                imgproc::threshold(
+                    &diff,
+                    &mut changed,
+                    PIXEL_DIFF_THRESHOLD,
+                    255.0,
+                    imgproc::THRESH_BINARY,
+                )?;
+
+                for area in &self.areas.as_ref().unwrap().areas {
+                    if area.mask_px == 0 {
+                        continue;
+                    }
+
+                    let mut changed_in_area = Mat::default();
+                    core::bitwise_and_def(&changed, &area.mask, &mut changed_in_area)?;
+
+                    let changed_pct = (core::count_non_zero(&changed_in_area)? as f32
+                        / area.mask_px as f32)
+                        * 100.0;
+
+                    if changed_pct >= area.threshold {
+                        motion_detected = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.prev_frame = Some(blurred);
+
+        Ok(motion_detected)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionState {
+    Idle,
+    /// Motion has just started, the pre-roll should be written before the current frame
+    Started,
+    Ongoing,
+    /// The post-roll has elapsed, the current frame is not part of the recording anymore
+    Ended,
+}
+
+/// Tracks whether a camera should currently be recording based on detected motion
+pub struct MotionTrigger {
+    detector: MotionDetector,
+    threshold: f32,
+    post_roll_frames: usize,
+    active: bool,
+    frames_since_motion: usize,
+}
+
+impl MotionTrigger {
+    pub fn new(config: &MotionConfig, fps: f64) -> Self {
+        Self {
+            detector: MotionDetector::default(),
+            threshold: config.threshold,
+            post_roll_frames: (config.post_roll_secs as f64 * fps) as usize,
+            active: false,
+            frames_since_motion: 0,
+        }
+    }
+
+    pub fn update(&mut self, frame: &Mat, zones: &[MotionZone]) -> opencv::Result<MotionState> {
+        let motion_detected = self.detector.update(frame, zones, self.threshold)?;
+
+        if motion_detected {
+            self.frames_since_motion = 0;
+        } else {
+            self.frames_since_motion = self.frames_since_motion.saturating_add(1);
+        }
+
+        Ok(match (self.active, motion_detected) {
+            (false, true) => {
+                self.active = true;
+                MotionState::Started
+            }
+            (false, false) => MotionState::Idle,
+            (true, _) if self.frames_since_motion > self.post_roll_frames => {
+                self.active = false;
+                MotionState::Ended
+            }
+            (true, _) => MotionState::Ongoing,
+        })
+    }
+}
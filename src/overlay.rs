@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local};
+use opencv::{
+    core::{Mat, MatTraitConst, Point, Rect, Scalar},
+    imgproc,
+};
+
+use crate::config::{OverlayConfig, OverlayPosition, VideoDeviceConfig};
+
+fn overlay_text(config: &VideoDeviceConfig, at: DateTime<Local>) -> String {
+    let mut text = String::new();
+
+    // A broken format string in the config shouldn't take recording down with it
+    if write!(text, "{}", at.format(&config.overlay.time_format)).is_err() {
+        text = at.format("%Y-%m-%d %H:%M:%S").to_string();
+    }
+
+    if config.overlay.show_camera_name {
+        text.push_str("  ");
+        text.push_str(&config.display_name());
+    }
+
+    text
+}
+
+fn draw_text_box(frame: &mut Mat, text: &str, overlay: &OverlayConfig) -> opencv::Result<()> {
+    let font_face = imgproc::FONT_HERSHEY_SIMPLEX;
+    let thickness = ((overlay.font_scale * 2.0).round() as i32).max(1);
+    let padding = ((overlay.font_scale * 8.0).round() as i32).max(2);
+
+    let mut baseline = 0;
+    let text_size = imgproc::get_text_size(
+        text,
+        font_face,
+        overlay.font_scale,
+        thickness,
+        &mut baseline,
+    )?;
+
+    let frame_size = frame.size()?;
+    let box_width = text_size.width + padding * 2;
+    let box_height = text_size.height + baseline + padding * 2;
+
+    let (x, y) = match overlay.position {
+        OverlayPosition::TopLeft => (0, 0),
+        OverlayPosition::TopRight => (frame_size.width - box_width, 0),
+        OverlayPosition::BottomLeft => (0, frame_size.height - box_height),
+        OverlayPosition::BottomRight => {
+            (frame_size.width - box_width, frame_size.height - box_height)
+        }
+    };
+
+    if overlay.background {
+        imgproc::rectangle(
+            frame,
+            Rect::new(x, y, box_width, box_height),
+            Scalar::new(0.0, 0.0, 0.0, 0.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+
+    imgproc::put_text(
+        frame,
+        text,
+        Point::new(x + padding, y + padding + text_size.height),
+        font_face,
+        overlay.font_scale,
+        Scalar::new(255.0, 255.0, 255.0, 0.0),
+        thickness,
+        imgproc::LINE_AA,
+        false,
+    )
+}
+
+/// Burns the timestamp and camera name into a frame
+pub fn draw_overlay(
+    frame: &mut Mat,
+    config: &VideoDeviceConfig,
+    at: DateTime<Local>,
+) -> opencv::Result<()> {
+    draw_text_box(frame, &overlay_text(config, at), &config.overlay)
+}
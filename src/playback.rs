@@ -0,0 +1,161 @@
+use std::error::Error as StdError;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use opencv::{
+    core::{Mat, MatTraitConst},
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::{
+    config::Config,
+    segments::{list_cam_segments, Segment},
+};
+
+/// Seeking is only worth it when the frame we want is further away than this, grabbing frames
+/// one by one is faster for short distances
+const MAX_GRAB_SECS: f64 = 4.0;
+
+/// Used for frame stepping when there is no footage to take the frame rate from
+pub const DEFAULT_FRAME_RATE: f64 = 30.0;
+
+struct OpenSegment {
+    segment_idx: usize,
+    vid_cap: videoio::VideoCapture,
+    frame_rate: f64,
+    /// Index of the frame the next read returns
+    next_frame_idx: i64,
+}
+
+/// Decodes a camera's segments by wall-clock time
+pub struct SegmentPlayer {
+    pub cam_idx: i32,
+    /// Sorted by their start time
+    pub segments: Vec<Segment>,
+    open_segment: Option<OpenSegment>,
+    frame: Mat,
+}
+
+impl SegmentPlayer {
+    /// Only segments which overlap `from` to `to` are played
+    pub fn new(
+        app_config: &Config,
+        cam_idx: i32,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+    ) -> Self {
+        Self {
+            cam_idx,
+            segments: list_cam_segments(app_config, cam_idx)
+                .into_iter()
+                .filter(|s| s.start < to && s.end() > from)
+                .collect(),
+            open_segment: None,
+            frame: Mat::default(),
+        }
+    }
+
+    fn segment_idx_at(&self, at: DateTime<Local>) -> Option<usize> {
+        let idx = self.segments.partition_point(|s| s.start <= at);
+
+        idx.checked_sub(1).filter(|i| self.segments[*i].covers(at))
+    }
+
+    pub fn frame_rate_at(&self, at: DateTime<Local>) -> Option<f64> {
+        let segment_idx = self.segment_idx_at(at)?;
+
+        match &self.open_segment {
+            Some(open_segment) if open_segment.segment_idx == segment_idx => {
+                Some(open_segment.frame_rate)
+            }
+            _ => self.segments[segment_idx]
+                .meta
+                .as_ref()
+                .map(|m| m.frame_rate as f64),
+        }
+    }
+
+    /// Start of the first footage after the given moment
+    pub fn next_footage_at(&self, at: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.segments
+            .iter()
+            .map(|s| s.start)
+            .find(|start| *start > at)
+    }
+
+    /// Decodes the frame shown at the given moment, `None` if there is no footage then
+    pub fn frame_at(&mut self, at: DateTime<Local>) -> Result<Option<&Mat>, Box<dyn StdError>> {
+        let Some(segment_idx) = self.segment_idx_at(at) else {
+            return Ok(None);
+        };
+        let segment = &self.segments[segment_idx];
+
+        if self
+            .open_segment
+            .as_ref()
+            .is_none_or(|o| o.segment_idx != segment_idx)
+        {
+            self.open_segment = None;
+
+            let vid_cap =
+                videoio::VideoCapture::from_file(segment.path.to_str().unwrap(), videoio::CAP_ANY)?;
+
+            if !vid_cap.is_opened()? {
+                return Err(anyhow!("Failed to open segment {:?}", segment.path).into());
+            }
+
+            let frame_rate = match &segment.meta {
+                Some(meta) if meta.frame_rate > 0 => meta.frame_rate as f64,
+                _ => vid_cap.get(videoio::CAP_PROP_FPS)?,
+            };
+
+            self.open_segment = Some(OpenSegment {
+                segment_idx,
+                vid_cap,
+                frame_rate: match frame_rate >= 1.0 {
+                    true => frame_rate,
+                    false => DEFAULT_FRAME_RATE,
+                },
+                next_frame_idx: 0,
+            });
+            self.frame = Mat::default();
+        }
+
+        let open_segment = self.open_segment.as_mut().unwrap();
+        let target_frame_idx = ((at - segment.start).num_microseconds().unwrap_or(0) as f64
+            * open_segment.frame_rate
+            / 1_000_000.0)
+            .round() as i64;
+
+        // Still showing it, e.g. while paused
+        if target_frame_idx == open_segment.next_frame_idx - 1 && !self.frame.empty() {
+            return Ok(Some(&self.frame));
+        }
+
+        let max_grab_frames = (open_segment.frame_rate * MAX_GRAB_SECS) as i64;
+
+        if target_frame_idx < open_segment.next_frame_idx
+            || target_frame_idx - open_segment.next_frame_idx > max_grab_frames
+        {
+            open_segment
+                .vid_cap
+                .set(videoio::CAP_PROP_POS_FRAMES, target_frame_idx as f64)?;
+            open_segment.next_frame_idx = target_frame_idx;
+        }
+
+        while open_segment.next_frame_idx < target_frame_idx {
+            if !open_segment.vid_cap.grab()? {
+                break;
+            }
+            open_segment.next_frame_idx += 1;
+        }
+
+        // The sidecar's end can be a little past the last frame
+        if !open_segment.vid_cap.read(&mut self.frame)? || self.frame.empty() {
+            return Ok(None);
+        }
+        open_segment.next_frame_idx += 1;
+
+        Ok(Some(&self.frame))
+    }
+}
@@ -0,0 +1,103 @@
+use opencv::{
+    core::{self, Mat, MatTraitConst, Point, Rect, Scalar, Size, Vector},
+    imgproc,
+};
+
+use crate::{
+    config::{PrivacyMask, PrivacyMaskStyle},
+    motion::MIN_ZONE_POINTS,
+};
+
+/// Smallest size of the blocks a pixelated mask is made of, in pixels
+const MIN_PIXELATE_BLOCK_SIZE: i32 = 16;
+
+/// A pixelated mask is at most this many blocks across its shorter side, so it stays unreadable
+/// at high resolutions
+const MAX_PIXELATE_BLOCKS: i32 = 8;
+
+fn pixelate_polygon(frame: &mut Mat, polygons: &Vector<Vector<Point>>) -> opencv::Result<()> {
+    let frame_size = frame.size()?;
+    let bounds = imgproc::bounding_rect(&polygons.get(0)?)?
+        & Rect::new(0, 0, frame_size.width, frame_size.height);
+
+    if bounds.empty() {
+        return Ok(());
+    }
+
+    let block_size =
+        MIN_PIXELATE_BLOCK_SIZE.max(bounds.width.min(bounds.height) / MAX_PIXELATE_BLOCKS);
+
+    let mut blocks = Mat::default();
+    imgproc::resize(
+        &frame.roi(bounds)?,
+        &mut blocks,
+        Size::new(
+            (bounds.width / block_size).max(1),
+            (bounds.height / block_size).max(1),
+        ),
+        0.0,
+        0.0,
+        imgproc::InterpolationFlags::INTER_AREA as i32,
+    )?;
+
+    let mut pixelated = Mat::default();
+    imgproc::resize(
+        &blocks,
+        &mut pixelated,
+        bounds.size(),
+        0.0,
+        0.0,
+        imgproc::InterpolationFlags::INTER_NEAREST as i32,
+    )?;
+
+    // Only copy back what's inside of the polygon, not its whole bounding box
+    let mut polygon_mask = Mat::new_rows_cols_with_default(
+        bounds.height,
+        bounds.width,
+        core::CV_8UC1,
+        Scalar::all(0.0),
+    )?;
+    imgproc::fill_poly(
+        &mut polygon_mask,
+        polygons,
+        Scalar::all(255.0),
+        imgproc::LINE_8,
+        0,
+        Point::new(-bounds.x, -bounds.y),
+    )?;
+
+    let mut frame_roi = frame.roi_mut(bounds)?;
+    pixelated.copy_to_masked(&mut frame_roi, &polygon_mask)
+}
+
+/// Blacks out or pixelates the masked regions of a frame in place
+pub fn apply_privacy_masks(frame: &mut Mat, masks: &[PrivacyMask]) -> opencv::Result<()> {
+    if masks.is_empty() {
+        return Ok(());
+    }
+
+    let frame_size = frame.size()?;
+
+    for mask in masks.iter().filter(|m| m.points.len() >= MIN_ZONE_POINTS) {
+        let polygon: Vector<Point> = mask
+            .points
+            .iter()
+            .map(|(x, y)| {
+                Point::new(
+                    (x * frame_size.width as f32) as i32,
+                    (y * frame_size.height as f32) as i32,
+                )
+            })
+            .collect();
+        let polygons = Vector::<Vector<Point>>::from_iter([polygon]);
+
+        match mask.style {
+            PrivacyMaskStyle::Fill => {
+                imgproc::fill_poly_def(frame, &polygons, Scalar::all(0.0))?;
+            }
+            PrivacyMaskStyle::Pixelate => pixelate_polygon(frame, &polygons)?,
+        }
+    }
+
+    Ok(())
+}
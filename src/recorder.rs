@@ -0,0 +1,323 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    sync::{atomic, Arc, Condvar, Mutex},
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use opencv::core::Mat;
+
+use crate::{
+    config::{Config, QueueFullPolicy, SegmentTag},
+    events::{log_event, EventKind},
+    overlay::draw_overlay,
+    segments::{parse_segment_start, write_segment_meta, SegmentMeta},
+    thumbnails::generate_thumbnails,
+    utils::video::VideoWriter,
+    video::{get_video_chunk_path, VideoCam},
+};
+
+enum RecorderMsg {
+    /// A frame along with when it was captured
+    Frame(Mat, DateTime<Local>),
+    /// Tags the segment currently being recorded, or the next one if there is none
+    Tag(SegmentTag),
+    FinishSegment,
+    Shutdown,
+}
+
+/// Bounded queue between the capture loop and the recording worker. Only frames count towards the
+/// capacity, control messages are always accepted so a segment can't be left unfinished.
+struct RecorderQueue {
+    msgs: Mutex<VecDeque<RecorderMsg>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: QueueFullPolicy,
+}
+
+impl RecorderQueue {
+    fn new(capacity: usize, policy: QueueFullPolicy) -> Self {
+        Self {
+            msgs: Mutex::new(VecDeque::new()),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    /// Returns how many frames were dropped to honour the queue's capacity
+    fn push_frame(&self, frame: Mat, captured_at: DateTime<Local>) -> usize {
+        let mut msgs = self.msgs.lock().unwrap();
+        let mut dropped = 0;
+
+        while msgs.len() >= self.capacity {
+            match self.policy {
+                QueueFullPolicy::DropNewest => return 1,
+                QueueFullPolicy::DropOldest => {
+                    let oldest_frame_pos = msgs
+                        .iter()
+                        .position(|msg| matches!(msg, RecorderMsg::Frame(..)));
+
+                    match oldest_frame_pos {
+                        Some(pos) => {
+                            msgs.remove(pos);
+                            dropped += 1;
+                        }
+                        None => break,
+                    }
+                }
+                QueueFullPolicy::Block => {
+                    msgs = self.not_full.wait(msgs).unwrap();
+                }
+            }
+        }
+
+        msgs.push_back(RecorderMsg::Frame(frame, captured_at));
+        self.not_empty.notify_one();
+
+        dropped
+    }
+
+    fn push_control(&self, msg: RecorderMsg) {
+        self.msgs.lock().unwrap().push_back(msg);
+        self.not_empty.notify_one();
+    }
+
+    fn pop(&self) -> RecorderMsg {
+        let mut msgs = self.msgs.lock().unwrap();
+
+        loop {
+            if let Some(msg) = msgs.pop_front() {
+                self.not_full.notify_one();
+                return msg;
+            }
+
+            msgs = self.not_empty.wait(msgs).unwrap();
+        }
+    }
+}
+
+/// Dedicated thread per camera which writes frames to disk and finalizes segments
+pub struct RecordingWorker {
+    cam: Arc<VideoCam>,
+    queue: Arc<RecorderQueue>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+    /// Time between two recorded frames when recording below the camera's frame rate
+    record_interval: Option<chrono::Duration>,
+    next_record_at: Mutex<Option<DateTime<Local>>>,
+}
+
+impl RecordingWorker {
+    /// `frame_rate` is the camera's, segments are encoded at `record_fps` if it's lower
+    pub fn spawn(app_config: Config, cam: Arc<VideoCam>, frame_rate: usize) -> Self {
+        let record_fps = cam
+            .config
+            .recording
+            .record_fps
+            .filter(|fps| *fps > 0 && *fps < frame_rate);
+
+        let frame_rate = record_fps.unwrap_or(frame_rate);
+        let record_interval =
+            record_fps.map(|fps| chrono::Duration::microseconds(1_000_000 / fps as i64));
+
+        let queue = Arc::new(RecorderQueue::new(
+            cam.config.recording.max_queued_frames,
+            cam.config.recording.queue_full_policy,
+        ));
+
+        let thread_handle = {
+            let cam = cam.clone();
+            let queue = queue.clone();
+
+            thread::spawn(move || run_worker(app_config, cam, queue, frame_rate))
+        };
+
+        Self {
+            cam,
+            queue,
+            thread_handle: Some(thread_handle),
+            record_interval,
+            next_record_at: Mutex::new(None),
+        }
+    }
+
+    /// Whether a frame captured at the given moment should be recorded, sampling by capture time
+    /// keeps the recorded frame rate steady even if the camera's isn't
+    fn should_record(&self, captured_at: DateTime<Local>) -> bool {
+        let Some(record_interval) = self.record_interval else {
+            return true;
+        };
+
+        let mut next_record_at = self.next_record_at.lock().unwrap();
+
+        if next_record_at.is_some_and(|n| captured_at < n) {
+            return false;
+        }
+
+        // After a gap, e.g. no motion for a while, start sampling from this frame again
+        *next_record_at = Some(match *next_record_at {
+            Some(n) if captured_at - n < record_interval => n + record_interval,
+            _ => captured_at + record_interval,
+        });
+
+        true
+    }
+
+    pub fn write_frame(&self, frame: &Mat, captured_at: DateTime<Local>) {
+        if !self.should_record(captured_at) {
+            return;
+        }
+
+        let dropped = self.queue.push_frame(frame.clone(), captured_at);
+
+        if dropped > 0 {
+            self.cam
+                .dropped_frames
+                .fetch_add(dropped, atomic::Ordering::Relaxed);
+        }
+    }
+
+    pub fn tag_segment(&self, tag: SegmentTag) {
+        self.queue.push_control(RecorderMsg::Tag(tag));
+    }
+
+    /// Finalize the current segment, the next frame written will start a new one
+    pub fn finish_segment(&self) {
+        self.queue.push_control(RecorderMsg::FinishSegment);
+    }
+}
+
+impl Drop for RecordingWorker {
+    fn drop(&mut self) {
+        self.queue.push_control(RecorderMsg::Shutdown);
+
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+    }
+}
+
+fn finish_video_writer(cam_idx: i32, video_writer: Option<VideoWriter>, tags: Vec<SegmentTag>) {
+    if let Some(mut video_writer) = video_writer {
+        thread::spawn(move || match video_writer.finish() {
+            Ok(segment_path) => {
+                let start = segment_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(parse_segment_start)
+                    .unwrap_or_else(Local::now);
+
+                let meta = SegmentMeta {
+                    cam_idx,
+                    start,
+                    end: start
+                        + chrono::Duration::milliseconds(
+                            (video_writer.frame_count() * 1000 / video_writer.frame_rate()) as i64,
+                        ),
+                    frame_count: video_writer.frame_count(),
+                    frame_rate: video_writer.frame_rate(),
+                    archive_tier: None,
+                    tags,
+                };
+
+                if let Err(error) = write_segment_meta(&segment_path, &meta) {
+                    println!("Failed to write segment metadata: {}", error);
+                }
+
+                log_event(
+                    EventKind::SegmentFinalized,
+                    Some(cam_idx),
+                    segment_path.to_string_lossy(),
+                );
+
+                if let Err(error) = generate_thumbnails(&segment_path, &meta) {
+                    println!("Failed to generate segment thumbnails: {}", error);
+                }
+            }
+            Err(error) => {
+                println!("Failed to finalize video clip: {}", error);
+                log_event(EventKind::EncodeFailure, Some(cam_idx), error.to_string());
+            }
+        });
+    }
+}
+
+fn run_worker(
+    app_config: Config,
+    cam: Arc<VideoCam>,
+    queue: Arc<RecorderQueue>,
+    frame_rate: usize,
+) {
+    let mut video_writer: Option<VideoWriter> = None;
+    let mut segment_tags: Vec<SegmentTag> = Vec::new();
+    // Only logged once per segment, every frame of it fails the same way
+    let mut chunk_dir_error_logged = false;
+
+    loop {
+        match queue.pop() {
+            RecorderMsg::Frame(mut frame, captured_at) => {
+                if video_writer.is_none() {
+                    // Named after the first frame, which can be a pre-roll frame from seconds ago
+                    let path = get_video_chunk_path(&app_config, cam.clone(), captured_at);
+
+                    if let Err(error) = fs::create_dir_all(&path) {
+                        if !chunk_dir_error_logged {
+                            println!(
+                                "Failed to create video chunk directory {:?}: {}",
+                                path, error
+                            );
+                            chunk_dir_error_logged = true;
+                        }
+
+                        cam.dropped_frames.fetch_add(1, atomic::Ordering::Relaxed);
+                        continue;
+                    }
+
+                    video_writer = Some(VideoWriter::new(path, frame_rate));
+                }
+
+                if cam.config.overlay.recording {
+                    if let Err(error) = draw_overlay(&mut frame, &cam.config, captured_at) {
+                        println!(
+                            "Failed to draw overlay for video device {}: {}",
+                            cam.config.idx, error
+                        );
+                    }
+                }
+
+                let res = video_writer.as_mut().unwrap().write(&frame);
+
+                if let Err(error) = res {
+                    println!(
+                        "Failed to write frame for video device {}: {}",
+                        cam.config.idx, error
+                    );
+                }
+            }
+            RecorderMsg::Tag(tag) => {
+                if !segment_tags.contains(&tag) {
+                    segment_tags.push(tag);
+                }
+            }
+            RecorderMsg::FinishSegment => {
+                chunk_dir_error_logged = false;
+                finish_video_writer(
+                    cam.config.idx,
+                    video_writer.take(),
+                    std::mem::take(&mut segment_tags),
+                );
+            }
+            RecorderMsg::Shutdown => {
+                finish_video_writer(
+                    cam.config.idx,
+                    video_writer.take(),
+                    std::mem::take(&mut segment_tags),
+                );
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime};
+
+use crate::{
+    config::{
+        Location, RecordingMode, RecordingSchedule, ScheduleRule, ScheduleTime,
+        VideoDeviceRecordingConfig,
+    },
+    utils::sun::sunrise_sunset,
+};
+
+/// Works out the local time of day a schedule time falls on for the given date, sun-relative
+/// times need a location and don't exist during polar day or night
+fn resolve_schedule_time(
+    schedule_time: ScheduleTime,
+    date: NaiveDate,
+    location: Option<Location>,
+) -> Option<NaiveTime> {
+    if let ScheduleTime::Fixed(time) = schedule_time {
+        return Some(time);
+    }
+
+    let location = location?;
+    let (sunrise, sunset) = sunrise_sunset(date, location.latitude, location.longitude)?;
+
+    let (sun_time, offset) = match schedule_time {
+        ScheduleTime::Sunrise(offset) => (sunrise, offset),
+        ScheduleTime::Sunset(offset) => (sunset, offset),
+        ScheduleTime::Fixed(_) => unreachable!(),
+    };
+
+    Some(
+        (sun_time + chrono::Duration::minutes(offset))
+            .with_timezone(&Local)
+            .time(),
+    )
+}
+
+fn rule_applies(rule: &ScheduleRule, at: DateTime<Local>, location: Option<Location>) -> bool {
+    let date = at.date_naive();
+    let weekday = at.weekday();
+    let time = at.time();
+
+    let (Some(start), Some(end)) = (
+        resolve_schedule_time(rule.start, date, location),
+        resolve_schedule_time(rule.end, date, location),
+    ) else {
+        return false;
+    };
+
+    if start <= end {
+        rule.days.contains(&weekday) && start <= time && time < end
+    } else {
+        // Overnight, e.g. 22:00 - 06:00 on a Friday also covers early Saturday
+        (rule.days.contains(&weekday) && time >= start)
+            || (rule.days.contains(&weekday.pred()) && time < end)
+    }
+}
+
+fn scheduled_mode(
+    schedule: &RecordingSchedule,
+    at: DateTime<Local>,
+    location: Option<Location>,
+) -> Option<RecordingMode> {
+    let exception = schedule
+        .exceptions
+        .iter()
+        .find(|e| e.date == at.date_naive());
+
+    if let Some(exception) = exception {
+        return Some(exception.mode);
+    }
+
+    schedule
+        .rules
+        .iter()
+        .find(|r| rule_applies(r, at, location))
+        .map(|r| r.mode)
+}
+
+/// Recording mode a camera should be in at the given moment
+pub fn recording_mode_at(
+    recording_config: &VideoDeviceRecordingConfig,
+    location: Option<Location>,
+    at: DateTime<Local>,
+) -> RecordingMode {
+    recording_config
+        .schedule
+        .as_ref()
+        .and_then(|s| scheduled_mode(s, at, location))
+        .unwrap_or(recording_config.mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Weekday};
+
+    use super::*;
+    use crate::config::ScheduleException;
+
+    fn fixed(hour: u32, minute: u32) -> ScheduleTime {
+        ScheduleTime::Fixed(NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    /// Motion recording from Friday 22:00 to Saturday 06:00
+    fn friday_night_schedule() -> RecordingSchedule {
+        RecordingSchedule {
+            rules: vec![ScheduleRule {
+                days: vec![Weekday::Fri],
+                start: fixed(22, 0),
+                end: fixed(6, 0),
+                mode: RecordingMode::Motion,
+            }],
+            exceptions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn overnight_rule_covers_both_sides_of_midnight() {
+        let schedule = friday_night_schedule();
+
+        // 2024-10-11 is a Friday
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 11, 23, 0), None),
+            Some(RecordingMode::Motion)
+        );
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 12, 5, 0), None),
+            Some(RecordingMode::Motion)
+        );
+    }
+
+    #[test]
+    fn overnight_rule_ends_and_starts_on_time() {
+        let schedule = friday_night_schedule();
+
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 11, 21, 59), None),
+            None
+        );
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 12, 6, 0), None),
+            None
+        );
+        // The early hours of Friday belong to Thursday night, which has no rule
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 11, 5, 0), None),
+            None
+        );
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 12, 23, 0), None),
+            None
+        );
+    }
+
+    #[test]
+    fn exceptions_take_priority_over_rules() {
+        let mut schedule = friday_night_schedule();
+        schedule.exceptions.push(ScheduleException {
+            date: NaiveDate::from_ymd_opt(2024, 10, 11).unwrap(),
+            mode: RecordingMode::Off,
+        });
+
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 11, 23, 0), None),
+            Some(RecordingMode::Off)
+        );
+        // Outside of any rule, the exception still applies for the whole day
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 11, 12, 0), None),
+            Some(RecordingMode::Off)
+        );
+        // The exception is for Friday only, the rule carries on past midnight
+        assert_eq!(
+            scheduled_mode(&schedule, at(2024, 10, 12, 5, 0), None),
+            Some(RecordingMode::Motion)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    error::Error as StdError,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, SegmentTag};
+
+/// Format of timestamps in file names, e.g. `18.10.2026-14.03.59`
+pub const SEGMENT_TIME_FORMAT: &str = "%d.%m.%Y-%H.%M.%S";
+
+/// Format of the timestamp in segment names, e.g. `rec-18.10.2026-14.03.59.250`. The milliseconds
+/// keep segments starting within the same second apart, segments named before they were added
+/// still parse
+const SEGMENT_NAME_TIME_FORMAT: &str = "%d.%m.%Y-%H.%M.%S%.3f";
+
+/// Segments are cut after this many seconds at the latest
+pub const MAX_SEGMENT_SECS: i64 = 60 * 4;
+
+/// Sidecar written next to every finalized segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentMeta {
+    pub cam_idx: i32,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub frame_count: usize,
+    pub frame_rate: usize,
+    /// Index of the archive tier last applied to the segment
+    #[serde(default)]
+    pub archive_tier: Option<usize>,
+    #[serde(default)]
+    pub tags: Vec<SegmentTag>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub cam_idx: i32,
+    /// Path to the finalized mp4 file
+    pub path: PathBuf,
+    pub start: DateTime<Local>,
+    pub meta: Option<SegmentMeta>,
+}
+
+impl Segment {
+    /// Segments finalized before sidecars existed are assumed to be as long as possible
+    pub fn end(&self) -> DateTime<Local> {
+        match &self.meta {
+            Some(meta) => meta.end,
+            None => self.start + chrono::Duration::seconds(MAX_SEGMENT_SECS),
+        }
+    }
+
+    pub fn covers(&self, at: DateTime<Local>) -> bool {
+        self.start <= at && at < self.end()
+    }
+}
+
+pub fn cam_recordings_dir(recordings_dir: &Path, cam_idx: i32) -> PathBuf {
+    recordings_dir.join(format!("cam-{}", cam_idx))
+}
+
+pub fn segment_name(start: DateTime<Local>) -> String {
+    format!("rec-{}", start.format(SEGMENT_NAME_TIME_FORMAT))
+}
+
+/// Parses the start time out of a segment's file stem
+pub fn parse_segment_start(file_stem: &str) -> Option<DateTime<Local>> {
+    let timestamp = file_stem.strip_prefix("rec-")?;
+    let naive = NaiveDateTime::parse_from_str(timestamp, SEGMENT_NAME_TIME_FORMAT).ok()?;
+
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// Name and start of the segment a file or directory belongs to, going by the segment name its
+/// own name starts with, e.g. `rec-18.10.2026-14.03.59.250.json`
+pub fn parse_segment_name_prefix(file_name: &str) -> Option<(&str, DateTime<Local>)> {
+    let mut names = file_name
+        .match_indices('.')
+        .map(|(idx, _)| &file_name[..idx])
+        .chain([file_name])
+        .collect::<Vec<_>>();
+    names.reverse();
+
+    names
+        .into_iter()
+        .find_map(|name| Some((name, parse_segment_start(name)?)))
+}
+
+pub fn segment_meta_path(segment_path: &Path) -> PathBuf {
+    segment_path.with_extension("json")
+}
+
+/// Strip of thumbnails taken every `THUMBNAIL_INTERVAL_SECS` of the segment
+pub fn segment_thumbnails_path(segment_path: &Path) -> PathBuf {
+    segment_path.with_extension("thumbs.jpg")
+}
+
+pub fn segment_poster_path(segment_path: &Path) -> PathBuf {
+    segment_path.with_extension("poster.jpg")
+}
+
+/// Every file besides the video which belongs to a segment, whether it exists or not
+pub fn segment_sidecar_paths(segment_path: &Path) -> Vec<PathBuf> {
+    vec![
+        segment_meta_path(segment_path),
+        segment_thumbnails_path(segment_path),
+        segment_poster_path(segment_path),
+    ]
+}
+
+/// Camera index out of a camera's recordings directory name
+pub fn parse_cam_dir_idx(dir_name: &str) -> Option<i32> {
+    dir_name.strip_prefix("cam-")?.parse().ok()
+}
+
+/// Recordings directories of every camera which has any, including removed cameras
+pub fn list_cam_dirs(recordings_dir: &Path) -> Vec<(i32, PathBuf)> {
+    let Ok(dir_entries) = fs::read_dir(recordings_dir) else {
+        return Vec::new();
+    };
+
+    let mut cam_dirs = dir_entries
+        .filter_map(|dir_entry| {
+            let path = dir_entry.ok()?.path();
+
+            if !path.is_dir() {
+                return None;
+            }
+
+            Some((parse_cam_dir_idx(path.file_name()?.to_str()?)?, path))
+        })
+        .collect::<Vec<_>>();
+
+    cam_dirs.sort_by_key(|(cam_idx, _)| *cam_idx);
+
+    cam_dirs
+}
+
+/// The recordings directory and every archive directory, all of them are laid out the same
+pub fn segment_roots(config: &Config) -> Vec<PathBuf> {
+    let mut roots = vec![config.recordings_dir.clone()];
+    roots.extend(config.cleaner.archive_dirs());
+
+    roots
+}
+
+pub fn write_segment_meta(
+    segment_path: &Path,
+    meta: &SegmentMeta,
+) -> Result<(), Box<dyn StdError>> {
+    let file = File::create(segment_meta_path(segment_path))?;
+    serde_json::to_writer_pretty(file, meta)?;
+
+    Ok(())
+}
+
+fn read_segment_meta(segment_path: &Path) -> Option<SegmentMeta> {
+    let file = File::open(segment_meta_path(segment_path)).ok()?;
+    serde_json::from_reader(file).ok()
+}
+
+/// Finalized segments of a camera, sorted by their start time
+pub fn list_segments(recordings_dir: &Path, cam_idx: i32) -> Vec<Segment> {
+    let Ok(dir_entries) = fs::read_dir(cam_recordings_dir(recordings_dir, cam_idx)) else {
+        return Vec::new();
+    };
+
+    let mut segments = dir_entries
+        .filter_map(|dir_entry| {
+            let path = dir_entry.ok()?.path();
+
+            if path.extension().is_none_or(|ext| ext != "mp4") {
+                return None;
+            }
+
+            let start = parse_segment_start(path.file_stem()?.to_str()?)?;
+
+            Some(Segment {
+                cam_idx,
+                meta: read_segment_meta(&path),
+                path,
+                start,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    segments.sort_by_key(|s| s.start);
+
+    segments
+}
+
+/// Finalized segments of a camera in the recordings and every archive directory, sorted by their
+/// start time
+pub fn list_cam_segments(config: &Config, cam_idx: i32) -> Vec<Segment> {
+    let mut segments = segment_roots(config)
+        .iter()
+        .flat_map(|root| list_segments(root, cam_idx))
+        .collect::<Vec<_>>();
+    segments.sort_by_key(|s| s.start);
+
+    segments
+}
+
+/// Finalized segments of every camera in a recordings or archive directory
+pub fn list_all_segments(recordings_dir: &Path) -> Vec<Segment> {
+    list_cam_dirs(recordings_dir)
+        .into_iter()
+        .flat_map(|(cam_idx, _)| list_segments(recordings_dir, cam_idx))
+        .collect()
+}
+
+/// Finds the segment which was recording at the given moment in an already sorted list
+pub fn find_segment_at(segments: &[Segment], at: DateTime<Local>) -> Option<&Segment> {
+    let idx = segments.partition_point(|s| s.start <= at);
+
+    segments[..idx].last().filter(|s| s.covers(at))
+}
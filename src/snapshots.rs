@@ -0,0 +1,115 @@
+use std::{error::Error as StdError, fs, path::PathBuf, sync::Arc, thread, time::Duration};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use opencv::core::MatTraitConst;
+
+use crate::{config::Config, disk_guard::writing_paused, video::VideoCam, CamsMapping};
+
+/// Format of the timestamp in snapshot names, e.g. `snap-18.10.2026-14.03.59.250.jpg`
+pub const SNAPSHOT_TIME_FORMAT: &str = "%d.%m.%Y-%H.%M.%S%.3f";
+
+/// Parses the time a snapshot was taken out of its file stem
+pub fn parse_snapshot_time(file_stem: &str) -> Option<DateTime<Local>> {
+    let timestamp = file_stem.strip_prefix("snap-")?;
+    let naive = NaiveDateTime::parse_from_str(timestamp, SNAPSHOT_TIME_FORMAT).ok()?;
+
+    Local.from_local_datetime(&naive).earliest()
+}
+
+pub fn cam_snapshots_dir(app_config: &Config, cam_idx: i32) -> PathBuf {
+    app_config.snapshots_dir().join(format!("cam-{}", cam_idx))
+}
+
+/// Interval snapshots are kept apart from the ones taken by hand since they get cleaned up
+pub fn cam_interval_snapshots_dir(app_config: &Config, cam_idx: i32) -> PathBuf {
+    cam_snapshots_dir(app_config, cam_idx).join("interval")
+}
+
+fn save_snapshot_to(
+    app_config: &Config,
+    cam: &VideoCam,
+    dir: PathBuf,
+) -> Result<PathBuf, Box<dyn StdError>> {
+    // The preview frame is scaled down and may have its overlay drawn on
+    let frame = cam.full_frame.read().unwrap().clone();
+
+    if frame.empty() {
+        return Err(anyhow!("No frame to snapshot for video device {}", cam.config.idx).into());
+    }
+
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!(
+        "snap-{}.{}",
+        Local::now().format(SNAPSHOT_TIME_FORMAT),
+        app_config.snapshots.format.extension()
+    ));
+
+    let saved_successfully =
+        opencv::imgcodecs::imwrite_def(path.as_os_str().to_str().unwrap(), &frame)?;
+
+    if !saved_successfully {
+        return Err(anyhow!("Failed to save snapshot to path {:?}", path).into());
+    }
+
+    Ok(path)
+}
+
+/// Saves the camera's current frame to its snapshots directory
+pub fn save_snapshot(app_config: &Config, cam: &VideoCam) -> Result<PathBuf, Box<dyn StdError>> {
+    save_snapshot_to(
+        app_config,
+        cam,
+        cam_snapshots_dir(app_config, cam.config.idx),
+    )
+}
+
+pub fn save_all_snapshots(app_config: &Config, cams: &CamsMapping) {
+    for (cam, _) in cams.values() {
+        match save_snapshot(app_config, cam) {
+            Ok(path) => println!("Saved snapshot to {:?}", path),
+            Err(error) => println!(
+                "Failed to save snapshot for video device {}: {}",
+                cam.config.idx, error
+            ),
+        }
+    }
+}
+
+/// Saves a snapshot on the camera's configured interval, returns straight away if there is none
+pub fn take_interval_snapshots(app_config: Config, cam: Arc<VideoCam>) {
+    let Some(interval_secs) = cam.config.snapshot_interval_secs else {
+        return;
+    };
+
+    let mut last_frame_idx = 0;
+
+    loop {
+        thread::sleep(Duration::from_secs(interval_secs.max(1)));
+
+        if writing_paused() {
+            continue;
+        }
+
+        // Don't keep saving the same frame if the camera stopped delivering new ones
+        let frame_idx = cam.frame.read().unwrap().0;
+        if frame_idx == last_frame_idx {
+            continue;
+        }
+        last_frame_idx = frame_idx;
+
+        let res = save_snapshot_to(
+            &app_config,
+            &cam,
+            cam_interval_snapshots_dir(&app_config, cam.config.idx),
+        );
+
+        if let Err(error) = res {
+            println!(
+                "Failed to save interval snapshot for video device {}: {}",
+                cam.config.idx, error
+            );
+        }
+    }
+}
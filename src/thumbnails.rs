@@ -0,0 +1,95 @@
+use std::{error::Error as StdError, path::Path};
+
+use anyhow::anyhow;
+use opencv::{
+    core::{self, Mat, MatTraitConst, Size, Vector},
+    imgcodecs, imgproc,
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::segments::{segment_poster_path, segment_thumbnails_path, SegmentMeta};
+
+/// A thumbnail is taken every this many seconds of a segment
+pub const THUMBNAIL_INTERVAL_SECS: i64 = 10;
+
+/// Width of each thumbnail in a segment's strip
+pub const THUMBNAIL_WIDTH: i32 = 160;
+
+const POSTER_WIDTH: i32 = 320;
+
+fn resize_to_width(frame: &Mat, width: i32) -> Result<Mat, Box<dyn StdError>> {
+    let frame_size = frame.size()?;
+    let mut resized = Mat::default();
+
+    imgproc::resize(
+        frame,
+        &mut resized,
+        Size::new(width, frame_size.height * width / frame_size.width),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+
+    Ok(resized)
+}
+
+/// Writes a strip of thumbnails side by side next to the segment, one every
+/// `THUMBNAIL_INTERVAL_SECS`, and a poster frame taken from the middle of the segment
+pub fn generate_thumbnails(
+    segment_path: &Path,
+    meta: &SegmentMeta,
+) -> Result<(), Box<dyn StdError>> {
+    let mut vid_cap =
+        videoio::VideoCapture::from_file(segment_path.to_str().unwrap(), videoio::CAP_ANY)?;
+
+    if !vid_cap.is_opened()? {
+        return Err(anyhow!("Failed to open segment {:?}", segment_path).into());
+    }
+
+    let sample_every = (meta.frame_rate * THUMBNAIL_INTERVAL_SECS as usize).max(1);
+    let poster_frame_idx = meta.frame_count / 2 / sample_every * sample_every;
+
+    let mut thumbnails = Vector::<Mat>::new();
+    let mut poster = None;
+    let mut frame = Mat::default();
+
+    for frame_idx in 0.. {
+        // Only frames which are kept get decoded
+        if frame_idx % sample_every != 0 {
+            if !vid_cap.grab()? {
+                break;
+            }
+            continue;
+        }
+
+        if !vid_cap.read(&mut frame)? || frame.empty() {
+            break;
+        }
+
+        thumbnails.push(resize_to_width(&frame, THUMBNAIL_WIDTH)?);
+
+        if frame_idx == poster_frame_idx {
+            poster = Some(resize_to_width(&frame, POSTER_WIDTH)?);
+        }
+    }
+
+    if thumbnails.is_empty() {
+        return Err(anyhow!("Segment {:?} has no frames", segment_path).into());
+    }
+
+    let mut strip = Mat::default();
+    core::hconcat(&thumbnails, &mut strip)?;
+    imgcodecs::imwrite_def(
+        segment_thumbnails_path(segment_path).to_str().unwrap(),
+        &strip,
+    )?;
+
+    // The frame count can be off for segments cut short, the first thumbnail has to do then
+    let poster = match poster {
+        Some(poster) => poster,
+        None => thumbnails.get(0)?,
+    };
+    imgcodecs::imwrite_def(segment_poster_path(segment_path).to_str().unwrap(), &poster)?;
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::{error::Error as StdError, fs, path::PathBuf};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use opencv::{
+    core::{Mat, MatTraitConst, Size},
+    imgproc,
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::{
+    config::Config,
+    segments::{list_cam_segments, SEGMENT_TIME_FORMAT},
+    utils::video::VideoWriter,
+};
+
+#[derive(Debug, Clone)]
+pub struct TimelapseRequest {
+    pub cam_idx: i32,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    /// Seconds of footage between two frames of the timelapse
+    pub sample_interval_secs: u64,
+    pub frame_rate: usize,
+}
+
+pub fn timelapses_dir(app_config: &Config) -> PathBuf {
+    app_config.recordings_dir.join("timelapses")
+}
+
+/// Samples one frame every `sample_interval_secs` out of the camera's finished segments and
+/// encodes them into an mp4, returns the path of the encoded video
+pub fn generate_timelapse(
+    app_config: &Config,
+    request: &TimelapseRequest,
+) -> Result<PathBuf, Box<dyn StdError>> {
+    if request.from >= request.to {
+        return Err(anyhow!("Timelapse start has to be before its end").into());
+    }
+
+    let segments = list_cam_segments(app_config, request.cam_idx)
+        .into_iter()
+        .filter(|s| s.start < request.to && s.end() > request.from)
+        .collect::<Vec<_>>();
+
+    if segments.is_empty() {
+        return Err(anyhow!(
+            "No recorded footage for video device {} in the given time range",
+            request.cam_idx
+        )
+        .into());
+    }
+
+    let frames_path = timelapses_dir(app_config).join(format!(
+        "timelapse-cam-{}-{}-{}",
+        request.cam_idx,
+        request.from.format(SEGMENT_TIME_FORMAT),
+        request.to.format(SEGMENT_TIME_FORMAT)
+    ));
+
+    // Frames left by an earlier failed run would otherwise end up in this timelapse
+    if frames_path.exists() {
+        fs::remove_dir_all(&frames_path)?;
+    }
+    fs::create_dir_all(&frames_path)?;
+
+    let mut video_writer = VideoWriter::new(frames_path.clone(), request.frame_rate.max(1));
+    let sample_interval = chrono::Duration::seconds(request.sample_interval_secs.max(1) as i64);
+    let mut next_sample_at = request.from;
+    // Segments may have been recorded at different resolutions, ffmpeg wants them all the same
+    let mut frame_size: Option<Size> = None;
+
+    for segment in segments {
+        let mut vid_cap =
+            videoio::VideoCapture::from_file(segment.path.to_str().unwrap(), videoio::CAP_ANY)?;
+
+        if !vid_cap.is_opened()? {
+            println!("Failed to open segment {:?}, skipping it", segment.path);
+            continue;
+        }
+
+        let segment_fps = match &segment.meta {
+            Some(meta) => meta.frame_rate as f64,
+            None => vid_cap.get(videoio::CAP_PROP_FPS)?,
+        };
+
+        if segment_fps < 1.0 {
+            println!("Segment {:?} has no usable fps, skipping it", segment.path);
+            continue;
+        }
+
+        let mut segment_frame_idx: usize = 0;
+        let mut frame = Mat::default();
+
+        // Only decode the frames we actually need, grabbing the others is a lot cheaper
+        while vid_cap.grab()? {
+            let frame_at = segment.start
+                + chrono::Duration::milliseconds(
+                    (segment_frame_idx as f64 / segment_fps * 1000.0) as i64,
+                );
+            segment_frame_idx += 1;
+
+            if frame_at >= request.to {
+                break;
+            }
+
+            if frame_at < next_sample_at {
+                continue;
+            }
+
+            while next_sample_at <= frame_at {
+                next_sample_at += sample_interval;
+            }
+
+            if !vid_cap.retrieve(&mut frame, 0)? || frame.empty() {
+                continue;
+            }
+
+            let size = *frame_size.get_or_insert(frame.size()?);
+
+            if frame.size()? == size {
+                video_writer.write(&frame)?;
+            } else {
+                let mut resized_frame = Mat::default();
+                imgproc::resize(
+                    &frame,
+                    &mut resized_frame,
+                    size,
+                    0.0,
+                    0.0,
+                    imgproc::INTER_AREA,
+                )?;
+                video_writer.write(&resized_frame)?;
+            }
+        }
+    }
+
+    if video_writer.frame_count() == 0 {
+        fs::remove_dir_all(&frames_path)?;
+
+        return Err(anyhow!(
+            "No frames could be read for video device {} in the given time range",
+            request.cam_idx
+        )
+        .into());
+    }
+
+    video_writer.finish()
+}
@@ -0,0 +1,73 @@
+use opencv::core::{self, Mat, MatTraitConst, Rect, Size};
+
+use crate::config::{CropRect, FrameRotation, FrameTransform};
+
+fn crop_rect(crop: &CropRect, size: Size) -> Rect {
+    Rect::new(
+        (crop.x * size.width as f32) as i32,
+        (crop.y * size.height as f32) as i32,
+        (crop.width * size.width as f32) as i32,
+        (crop.height * size.height as f32) as i32,
+    ) & Rect::new(0, 0, size.width, size.height)
+}
+
+/// Size a frame of the given size ends up with once transformed
+pub fn transformed_size(size: Size, transform: &FrameTransform) -> Size {
+    let rotated_size = match transform.rotation {
+        FrameRotation::Clockwise90 | FrameRotation::Clockwise270 => {
+            Size::new(size.height, size.width)
+        }
+        FrameRotation::None | FrameRotation::Clockwise180 => size,
+    };
+
+    // A crop which ends up outside of the frame is ignored, same as in `apply_transform`
+    match transform
+        .crop
+        .map(|c| crop_rect(&c, rotated_size))
+        .filter(|r| !r.empty())
+    {
+        Some(rect) => rect.size(),
+        None => rotated_size,
+    }
+}
+
+/// Rotates, flips and crops a frame, in that order
+pub fn apply_transform(frame: &mut Mat, transform: &FrameTransform) -> opencv::Result<()> {
+    let rotate_code = match transform.rotation {
+        FrameRotation::None => None,
+        FrameRotation::Clockwise90 => Some(core::ROTATE_90_CLOCKWISE),
+        FrameRotation::Clockwise180 => Some(core::ROTATE_180),
+        FrameRotation::Clockwise270 => Some(core::ROTATE_90_COUNTERCLOCKWISE),
+    };
+
+    if let Some(rotate_code) = rotate_code {
+        let mut rotated_frame = Mat::default();
+        core::rotate(&*frame, &mut rotated_frame, rotate_code)?;
+        *frame = rotated_frame;
+    }
+
+    // 1 flips around the y axis, 0 around the x axis and -1 around both
+    let flip_code = match (transform.flip_horizontal, transform.flip_vertical) {
+        (false, false) => None,
+        (true, false) => Some(1),
+        (false, true) => Some(0),
+        (true, true) => Some(-1),
+    };
+
+    if let Some(flip_code) = flip_code {
+        let mut flipped_frame = Mat::default();
+        core::flip(&*frame, &mut flipped_frame, flip_code)?;
+        *frame = flipped_frame;
+    }
+
+    if let Some(crop) = &transform.crop {
+        let rect = crop_rect(crop, frame.size()?);
+
+        if !rect.empty() {
+            let cropped_frame = frame.roi(rect)?.try_clone()?;
+            *frame = cropped_frame;
+        }
+    }
+
+    Ok(())
+}
@@ -7,25 +7,20 @@ use eframe::egui::{self, vec2, Ui};
 use image::{ImageBuffer, Rgba};
 use opencv::core::{MatTraitConst, MatTraitConstManual};
 
-use crate::{video::VideoCam, SMApp};
-
-fn cam_to_egui_image<'b>(
-    ctx: &egui::Context,
-    cam: &'b Arc<VideoCam>,
-    image_uri: &String,
-) -> egui::Image<'b> {
-    let frame = cam.frame.read().unwrap();
+use crate::{
+    config::save_config,
+    snapshots::save_snapshot,
+    ui::zone_editor::{show_zone_overlay, ZoneEditor},
+    video::VideoCam,
+    SMApp,
+};
 
-    let frame_size = frame.1.size().unwrap();
+/// Converts a BGR frame into an image egui can display
+pub fn mat_to_color_image(frame: &opencv::core::Mat) -> egui::ColorImage {
+    let frame_size = frame.size().unwrap();
 
     let mut frame_rgba = opencv::core::Mat::default();
-    opencv::imgproc::cvt_color(
-        &frame.1,
-        &mut frame_rgba,
-        opencv::imgproc::COLOR_BGR2RGBA,
-        0,
-    )
-    .unwrap();
+    opencv::imgproc::cvt_color(frame, &mut frame_rgba, opencv::imgproc::COLOR_BGR2RGBA, 0).unwrap();
 
     let image_buf = ImageBuffer::<Rgba<u8>, _>::from_raw(
         frame_size.width as u32,
@@ -34,164 +29,349 @@ fn cam_to_egui_image<'b>(
     )
     .unwrap();
 
-    let egui_color_img = egui::ColorImage::from_rgba_unmultiplied(
+    egui::ColorImage::from_rgba_unmultiplied(
         [frame_size.width as usize, frame_size.height as usize],
         image_buf.as_flat_samples().samples,
-    );
+    )
+}
+
+fn cam_to_egui_image<'b>(
+    ctx: &egui::Context,
+    cam: &'b Arc<VideoCam>,
+    image_uri: &String,
+) -> egui::Image<'b> {
+    let egui_color_img = mat_to_color_image(&cam.frame.read().unwrap().1);
 
     let tex_handle = ctx.load_texture(image_uri, egui_color_img, egui::TextureOptions::default());
 
     egui::Image::from_texture(&tex_handle)
 }
 
-pub fn show_cam_grid(app: &SMApp, ctx: &egui::Context, ui: &mut Ui) {
+/// Red while frames are being written, grey while recording is enabled but e.g. waiting for
+/// motion
+fn show_rec_indicator(ui: &mut Ui, cam: &Arc<VideoCam>, image_rect: egui::Rect) {
+    if !cam.recording_enabled.load(atomic::Ordering::Relaxed) {
+        return;
+    }
+
+    let color = match cam.recording.load(atomic::Ordering::Relaxed) {
+        true => egui::Color32::RED,
+        false => egui::Color32::GRAY,
+    };
+
+    ui.painter().text(
+        image_rect.right_top() + vec2(-8.0, 8.0),
+        egui::Align2::RIGHT_TOP,
+        "● REC",
+        egui::FontId::proportional(16.0),
+        color,
+    );
+}
+
+/// Small buttons shown in the top left corner of a camera's preview
+fn show_cam_controls(app: &mut SMApp, ui: &mut Ui, cam: &Arc<VideoCam>, image_rect: egui::Rect) {
+    let mut controls_ui = ui.new_child(
+        egui::UiBuilder::new()
+            .max_rect(image_rect.shrink(4.0))
+            .layout(egui::Layout::left_to_right(egui::Align::Min)),
+    );
+
+    let editing_zones = app
+        .zone_editor
+        .as_ref()
+        .is_some_and(|e| e.cam_idx == cam.config.idx);
+
+    if controls_ui
+        .small_button(match editing_zones {
+            true => "Done",
+            false => "Zones",
+        })
+        .clicked()
+    {
+        app.zone_editor = match editing_zones {
+            true => None,
+            false => Some(ZoneEditor::new(
+                cam.config.idx,
+                cam.motion_zones.read().unwrap().clone(),
+            )),
+        };
+    }
+
+    let capture_enabled = cam.capture_enabled.load(atomic::Ordering::Relaxed);
+
+    if controls_ui
+        .small_button(match capture_enabled {
+            true => "Stop Capture",
+            false => "Start Capture",
+        })
+        .clicked()
+    {
+        cam.capture_enabled
+            .store(!capture_enabled, atomic::Ordering::Relaxed);
+    }
+
+    let recording_enabled = cam.recording_enabled.load(atomic::Ordering::Relaxed);
+
+    if controls_ui
+        .small_button(match recording_enabled {
+            true => "Stop Recording",
+            false => "Start Recording",
+        })
+        .clicked()
+    {
+        cam.recording_enabled
+            .store(!recording_enabled, atomic::Ordering::Relaxed);
+        cam.recording_started_manually
+            .store(!recording_enabled, atomic::Ordering::Relaxed);
+    }
+
+    // Recording changes are only kept for this run unless saved to the config
+    let configured_recording = app
+        .config
+        .video_devices
+        .iter_mut()
+        .find(|vdc| vdc.idx == cam.config.idx)
+        .map(|vdc| &mut vdc.recording);
+
+    if let Some(configured_recording) = configured_recording {
+        let recording_enabled = cam.recording_enabled.load(atomic::Ordering::Relaxed);
+
+        if configured_recording.enabled != recording_enabled
+            && controls_ui
+                .small_button("Save")
+                .on_hover_text("Keep recording enabled or disabled after a restart")
+                .clicked()
+        {
+            configured_recording.enabled = recording_enabled;
+            save_config(&app.config);
+        }
+    }
+
+    if controls_ui.small_button("Snapshot").clicked() {
+        match save_snapshot(&app.config, cam) {
+            Ok(path) => println!("Saved snapshot to {:?}", path),
+            Err(error) => println!(
+                "Failed to save snapshot for video device {}: {}",
+                cam.config.idx, error
+            ),
+        }
+    }
+}
+
+/// Lays items out in up to two columns, sized so the grid fills the available space.
+/// `show_item` gets the size each item should take up
+pub fn show_grid<T>(ui: &mut Ui, items: &[T], mut show_item: impl FnMut(&mut Ui, &T, egui::Vec2)) {
     let max_columns = 2;
     let column_gap = 4.0;
     let column_gap_padding_size = vec2(column_gap / 2.0, column_gap / 2.0);
 
     let grid_item_size = {
         let available_size = ui.available_size();
-        let columns_f32 = min(max_columns, app.cams.len()) as f32;
+        let columns_f32 = min(max_columns, items.len()) as f32;
 
         vec2(
             (available_size.x / columns_f32) - (column_gap / 2.0),
-            available_size.y / (app.cams.len() as f32 / columns_f32).ceil(),
+            available_size.y / (items.len() as f32 / columns_f32).ceil(),
         )
     };
 
     // TODO: Fix spacing / gap / padding idk
 
     ui.columns(max_columns, |cols| {
-        for (item_idx, (cam_idx, (cam, _))) in app.cams.iter().enumerate() {
+        for (item_idx, item) in items.iter().enumerate() {
             let ui = &mut cols[item_idx % cols.len()];
             ui.style_mut().spacing.indent = column_gap / 2.0;
 
-            let cam_frame = cam.frame.read().unwrap();
-
-            let frame_image_uri = &format!("bytes://cam-{}-frame.jpg", cam_idx);
-
-            if app.cams_paused {
-                let style = egui::Style::default();
-
-                ui.add_sized(
-                    grid_item_size - column_gap_padding_size,
-                    egui::Label::new(
-                        [
-                            egui::RichText::new("Paused")
-                                .size(24.0)
-                                .color(egui::Color32::LIGHT_GRAY),
-                            egui::RichText::new(format!("\n Camera {}", cam_idx))
-                                .size(16.0)
-                                .color(egui::Color32::GRAY),
-                        ]
-                        .into_iter()
-                        .fold(
-                            egui::text::LayoutJob::default(),
-                            |mut layout_job, line| {
-                                line.append_to(
-                                    &mut layout_job,
-                                    &style,
-                                    egui::FontSelection::Default,
-                                    egui::Align::Center,
-                                );
-                                layout_job
-                            },
-                        ),
-                    )
-                    .selectable(false),
-                );
-
-                continue;
-            }
-
-            if cam_frame.1.empty() {
-                let style = egui::Style::default();
-
-                ui.add_sized(
-                    grid_item_size - column_gap_padding_size,
-                    egui::Label::new(
-                        [
-                            egui::RichText::new("No Image")
-                                .size(24.0)
-                                .color(egui::Color32::RED),
-                            egui::RichText::new(format!("\n     Camera {}", cam_idx))
-                                .size(16.0)
-                                .color(egui::Color32::GRAY),
-                        ]
-                        .into_iter()
-                        .fold(
-                            egui::text::LayoutJob::default(),
-                            |mut layout_job, line| {
-                                line.append_to(
-                                    &mut layout_job,
-                                    &style,
-                                    egui::FontSelection::Default,
-                                    egui::Align::Center,
-                                );
-                                layout_job
-                            },
-                        ),
-                    )
-                    .selectable(false),
-                );
-
-                continue;
-            }
-
-            if cam.errored.load(atomic::Ordering::Relaxed) {
-                let style = egui::Style::default();
-
-                ui.add_sized(
-                    grid_item_size - column_gap_padding_size,
-                    egui::Label::new(
-                        [
-                            egui::RichText::new("Video Error")
-                                .size(24.0)
-                                .color(egui::Color32::RED),
-                            egui::RichText::new(format!("\n    Camera {}", cam_idx))
-                                .size(16.0)
-                                .color(egui::Color32::GRAY),
-                        ]
-                        .into_iter()
-                        .fold(
-                            egui::text::LayoutJob::default(),
-                            |mut layout_job, line| {
-                                line.append_to(
-                                    &mut layout_job,
-                                    &style,
-                                    egui::FontSelection::Default,
-                                    egui::Align::Center,
-                                );
-                                layout_job
-                            },
-                        ),
-                    )
-                    .selectable(false),
-                );
-
-                continue;
-            }
-
-            let frame_image = cam_to_egui_image(ctx, cam, frame_image_uri);
-            ctx.forget_image(frame_image_uri);
+            show_item(ui, item, grid_item_size - column_gap_padding_size);
+        }
+    });
+}
+
+pub fn show_cam_grid(app: &mut SMApp, ctx: &egui::Context, ui: &mut Ui) {
+    let cams = app
+        .cams
+        .iter()
+        .map(|(cam_idx, (cam, _))| (*cam_idx, cam.clone()))
+        .collect::<Vec<_>>();
+
+    show_grid(ui, &cams, |ui, (cam_idx, cam), item_size| {
+        let cam_frame = cam.frame.read().unwrap();
+
+        let frame_image_uri = &format!("bytes://cam-{}-frame.jpg", cam_idx);
+
+        if app.cams_paused {
+            let style = egui::Style::default();
+
+            ui.add_sized(
+                item_size,
+                egui::Label::new(
+                    [
+                        egui::RichText::new("Paused")
+                            .size(24.0)
+                            .color(egui::Color32::LIGHT_GRAY),
+                        egui::RichText::new(format!("\n Camera {}", cam_idx))
+                            .size(16.0)
+                            .color(egui::Color32::GRAY),
+                    ]
+                    .into_iter()
+                    .fold(
+                        egui::text::LayoutJob::default(),
+                        |mut layout_job, line| {
+                            line.append_to(
+                                &mut layout_job,
+                                &style,
+                                egui::FontSelection::Default,
+                                egui::Align::Center,
+                            );
+                            layout_job
+                        },
+                    ),
+                )
+                .selectable(false),
+            );
+
+            return;
+        }
+
+        if !cam.capture_enabled.load(atomic::Ordering::Relaxed) {
+            drop(cam_frame);
+
+            let style = egui::Style::default();
+
+            let placeholder_response = ui.add_sized(
+                item_size,
+                egui::Label::new(
+                    [
+                        egui::RichText::new("Capture Off")
+                            .size(24.0)
+                            .color(egui::Color32::LIGHT_GRAY),
+                        egui::RichText::new(format!("\n        Camera {}", cam_idx))
+                            .size(16.0)
+                            .color(egui::Color32::GRAY),
+                    ]
+                    .into_iter()
+                    .fold(
+                        egui::text::LayoutJob::default(),
+                        |mut layout_job, line| {
+                            line.append_to(
+                                &mut layout_job,
+                                &style,
+                                egui::FontSelection::Default,
+                                egui::Align::Center,
+                            );
+                            layout_job
+                        },
+                    ),
+                )
+                .selectable(false),
+            );
+
+            // Controls stay reachable so capture can be turned back on
+            show_cam_controls(app, ui, cam, placeholder_response.rect);
+
+            return;
+        }
+
+        if cam_frame.1.empty() {
+            let style = egui::Style::default();
+
+            ui.add_sized(
+                item_size,
+                egui::Label::new(
+                    [
+                        egui::RichText::new("No Image")
+                            .size(24.0)
+                            .color(egui::Color32::RED),
+                        egui::RichText::new(format!("\n     Camera {}", cam_idx))
+                            .size(16.0)
+                            .color(egui::Color32::GRAY),
+                    ]
+                    .into_iter()
+                    .fold(
+                        egui::text::LayoutJob::default(),
+                        |mut layout_job, line| {
+                            line.append_to(
+                                &mut layout_job,
+                                &style,
+                                egui::FontSelection::Default,
+                                egui::Align::Center,
+                            );
+                            layout_job
+                        },
+                    ),
+                )
+                .selectable(false),
+            );
+
+            return;
+        }
+
+        if cam.errored.load(atomic::Ordering::Relaxed) {
+            let style = egui::Style::default();
+
             ui.add_sized(
-                grid_item_size - column_gap_padding_size,
-                frame_image.fit_to_exact_size(grid_item_size),
+                item_size,
+                egui::Label::new(
+                    [
+                        egui::RichText::new("Video Error")
+                            .size(24.0)
+                            .color(egui::Color32::RED),
+                        egui::RichText::new(format!("\n    Camera {}", cam_idx))
+                            .size(16.0)
+                            .color(egui::Color32::GRAY),
+                    ]
+                    .into_iter()
+                    .fold(
+                        egui::text::LayoutJob::default(),
+                        |mut layout_job, line| {
+                            line.append_to(
+                                &mut layout_job,
+                                &style,
+                                egui::FontSelection::Default,
+                                egui::Align::Center,
+                            );
+                            layout_job
+                        },
+                    ),
+                )
+                .selectable(false),
             );
 
-            // TODO: Settings button
-            // if ui
-            //         .put(
-            //             egui::Rect {
-            //                 min: ui_image.rect.min,
-            //                 max: ui_image.rect.min + vec2(30.0, 10.0),
-            //             },
-            //             egui::Button::new("Wowza"),
-            //         )
-            //         .clicked()
-            // {
-            //     println!("clic");
-            // }
+            return;
         }
+
+        // Other readers of the frame below must not wait behind the capture thread's writes
+        drop(cam_frame);
+
+        let frame_image = cam_to_egui_image(ctx, cam, frame_image_uri);
+        ctx.forget_image(frame_image_uri);
+
+        let editing_zones = app
+            .zone_editor
+            .as_ref()
+            .is_some_and(|e| e.cam_idx == *cam_idx);
+
+        let image_response = ui
+            .add_sized(
+                item_size,
+                frame_image
+                    .fit_to_exact_size(item_size)
+                    .sense(match editing_zones {
+                        true => egui::Sense::click(),
+                        false => egui::Sense::hover(),
+                    }),
+            )
+            .on_hover_text(format!(
+                "Camera {}\nDropped frames: {}",
+                cam_idx,
+                cam.dropped_frames.load(atomic::Ordering::Relaxed)
+            ));
+
+        if let Some(zone_editor) = app.zone_editor.as_mut().filter(|_| editing_zones) {
+            show_zone_overlay(zone_editor, ui, &image_response);
+        }
+
+        show_rec_indicator(ui, cam, image_response.rect);
+        show_cam_controls(app, ui, cam, image_response.rect);
     });
 }
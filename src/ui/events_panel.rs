@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use eframe::egui::{self, Ui};
+
+use crate::{
+    config::Config,
+    events::{load_events, Event, EventKind},
+    locks::lock_segment,
+    segments::{find_segment_at, list_cam_segments, Segment},
+    utils::misc::open_path,
+    SMApp,
+};
+
+pub struct EventsPanel {
+    /// Newest first
+    events: Vec<Event>,
+    segments: BTreeMap<i32, Vec<Segment>>,
+    kind_filter: Option<EventKind>,
+    cam_filter: Option<i32>,
+}
+
+impl EventsPanel {
+    pub fn load(config: &Config) -> Self {
+        let mut events = load_events().unwrap_or_else(|error| {
+            println!("Failed to load events due to error: {error}");
+            Vec::new()
+        });
+        events.reverse();
+
+        let segments = config
+            .video_devices
+            .iter()
+            .map(|vdc| (vdc.idx, list_cam_segments(config, vdc.idx)))
+            .collect();
+
+        Self {
+            events,
+            segments,
+            kind_filter: None,
+            cam_filter: None,
+        }
+    }
+
+    fn event_segment(&self, event: &Event) -> Option<&Segment> {
+        find_segment_at(self.segments.get(&event.cam_idx?)?, event.at)
+    }
+}
+
+fn show_filters(panel: &mut EventsPanel, ui: &mut Ui) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Type")
+            .selected_text(panel.kind_filter.map(|k| k.label()).unwrap_or("All"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut panel.kind_filter, None, "All");
+
+                for kind in EventKind::ALL {
+                    ui.selectable_value(&mut panel.kind_filter, Some(kind), kind.label());
+                }
+            });
+
+        let cam_idxs = panel.segments.keys().copied().collect::<Vec<_>>();
+
+        egui::ComboBox::from_label("Camera")
+            .selected_text(
+                panel
+                    .cam_filter
+                    .map(|idx| format!("Camera {}", idx))
+                    .unwrap_or("All".to_string()),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut panel.cam_filter, None, "All");
+
+                for cam_idx in cam_idxs {
+                    ui.selectable_value(
+                        &mut panel.cam_filter,
+                        Some(cam_idx),
+                        format!("Camera {}", cam_idx),
+                    );
+                }
+            });
+    });
+}
+
+fn show_events(panel: &EventsPanel, ui: &mut Ui) {
+    let events = panel
+        .events
+        .iter()
+        .filter(|e| panel.kind_filter.is_none_or(|k| e.kind == k))
+        .filter(|e| panel.cam_filter.is_none_or(|idx| e.cam_idx == Some(idx)))
+        .collect::<Vec<_>>();
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("events_grid")
+            .striped(true)
+            .num_columns(5)
+            .show(ui, |ui| {
+                for event in events {
+                    ui.label(event.at.format("%d.%m.%Y %H:%M:%S").to_string());
+                    ui.label(
+                        event
+                            .cam_idx
+                            .map(|idx| format!("Camera {}", idx))
+                            .unwrap_or_default(),
+                    );
+                    ui.label(event.kind.label());
+                    ui.label(&event.details);
+
+                    match panel.event_segment(event) {
+                        Some(segment) => {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .link(segment.path.file_name().unwrap().to_string_lossy())
+                                    .clicked()
+                                {
+                                    open_path(&segment.path);
+                                }
+
+                                if ui
+                                    .small_button("Lock")
+                                    .on_hover_text("Keep this segment from being cleaned up")
+                                    .clicked()
+                                {
+                                    let reason = format!("{} event", event.kind.label());
+
+                                    match lock_segment(segment, Some(reason)) {
+                                        Ok(lock) => println!("Added lock {}", lock.id),
+                                        Err(error) => {
+                                            println!("Failed to lock segment: {}", error)
+                                        }
+                                    }
+                                }
+                            });
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+pub fn show_events_panel(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(panel) = &mut app.events_panel else {
+        return;
+    };
+
+    let mut open = true;
+    let mut refresh = false;
+
+    egui::Window::new("Events")
+        .open(&mut open)
+        .default_size((720.0, 480.0))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                show_filters(panel, ui);
+                refresh = ui.button("Refresh").clicked();
+            });
+            ui.separator();
+
+            show_events(panel, ui);
+        });
+
+    if refresh {
+        let mut new_panel = EventsPanel::load(&app.config);
+        new_panel.kind_filter = panel.kind_filter;
+        new_panel.cam_filter = panel.cam_filter;
+        *panel = new_panel;
+    }
+
+    if !open {
+        app.events_panel = None;
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone};
+use eframe::egui::{self, Ui};
+
+use crate::{
+    config::Config,
+    evidence::{current_user, export_evidence},
+    export::{export_clips, exportable_cam_idxs},
+    utils::misc::open_path,
+    SMApp,
+};
+
+/// What was exported, e.g. "Camera 2", and where to
+type ExportResults = Vec<(String, Result<PathBuf, String>)>;
+
+pub struct ExportWindow {
+    /// Cameras with footage, and whether each is exported
+    cams_selected: Vec<(i32, bool)>,
+    date: NaiveDate,
+    from_hour: u32,
+    from_minute: u32,
+    to_hour: u32,
+    to_minute: u32,
+    /// Exports a single package with a manifest and hashes instead of loose clips
+    evidence: bool,
+    exported_by: String,
+    /// Filled in by the worker thread once every clip is done, `None` until an export was started
+    results: Option<Arc<Mutex<Option<ExportResults>>>>,
+}
+
+impl ExportWindow {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            cams_selected: exportable_cam_idxs(config)
+                .into_iter()
+                .map(|idx| (idx, false))
+                .collect(),
+            date: Local::now().date_naive(),
+            from_hour: 0,
+            from_minute: 0,
+            to_hour: 1,
+            to_minute: 0,
+            evidence: false,
+            exported_by: current_user(),
+            results: None,
+        }
+    }
+
+    /// A range ending at or before its start runs past midnight
+    fn ends_next_day(&self) -> bool {
+        (self.to_hour, self.to_minute) <= (self.from_hour, self.from_minute)
+    }
+
+    fn time_range(&self) -> Option<(DateTime<Local>, DateTime<Local>)> {
+        let at = |date: NaiveDate, hour: u32, minute: u32| {
+            let naive = date.and_time(NaiveTime::from_hms_opt(hour, minute, 0)?);
+            Local.from_local_datetime(&naive).earliest()
+        };
+
+        let to_date = match self.ends_next_day() {
+            true => self.date.succ_opt()?,
+            false => self.date,
+        };
+
+        Some((
+            at(self.date, self.from_hour, self.from_minute)?,
+            at(to_date, self.to_hour, self.to_minute)?,
+        ))
+        .filter(|(from, to)| from < to)
+    }
+
+    fn selected_cam_idxs(&self) -> Vec<i32> {
+        self.cams_selected
+            .iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(idx, _)| *idx)
+            .collect()
+    }
+}
+
+fn show_time(ui: &mut Ui, hour: &mut u32, minute: &mut u32) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(hour).range(0..=23));
+        ui.label(":");
+        ui.add(
+            egui::DragValue::new(minute)
+                .range(0..=59)
+                .custom_formatter(|n, _| format!("{:02}", n)),
+        );
+    });
+}
+
+fn show_results(ui: &mut Ui, results: &ExportResults) {
+    for (label, result) in results {
+        ui.horizontal(|ui| {
+            ui.label(label);
+
+            match result {
+                Ok(path) => {
+                    if ui
+                        .link(path.file_name().unwrap().to_string_lossy())
+                        .clicked()
+                    {
+                        open_path(path);
+                    }
+                }
+                Err(error) => {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            }
+        });
+    }
+}
+
+pub fn show_export_window(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(window) = &mut app.export_window else {
+        return;
+    };
+
+    let mut open = true;
+
+    egui::Window::new("Export Clips")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("export_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Cameras");
+                    ui.horizontal_wrapped(|ui| {
+                        for (cam_idx, selected) in &mut window.cams_selected {
+                            ui.checkbox(selected, format!("Camera {}", cam_idx));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Day");
+                    ui.add(
+                        egui_extras::DatePickerButton::new(&mut window.date).id_salt("export_date"),
+                    );
+                    ui.end_row();
+
+                    ui.label("From");
+                    show_time(ui, &mut window.from_hour, &mut window.from_minute);
+                    ui.end_row();
+
+                    ui.label("To");
+                    ui.horizontal(|ui| {
+                        show_time(ui, &mut window.to_hour, &mut window.to_minute);
+
+                        if window.ends_next_day() {
+                            ui.label("next day");
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.checkbox(&mut window.evidence, "Evidence package")
+                        .on_hover_text("Adds a manifest with hashes which can be verified later");
+                    ui.add_enabled(
+                        window.evidence,
+                        egui::TextEdit::singleline(&mut window.exported_by)
+                            .hint_text("Exported by"),
+                    );
+                    ui.end_row();
+                });
+
+            ui.separator();
+
+            let results = window.results.as_ref().map(|r| r.lock().unwrap().clone());
+            let exporting = matches!(results, Some(None));
+            let time_range = window.time_range();
+            let cam_idxs = window.selected_cam_idxs();
+
+            if ui
+                .add_enabled(
+                    time_range.is_some()
+                        && !cam_idxs.is_empty()
+                        && !(window.evidence && window.exported_by.trim().is_empty())
+                        && !exporting,
+                    egui::Button::new("Export"),
+                )
+                .clicked()
+            {
+                let results = Arc::new(Mutex::new(None));
+                window.results = Some(results.clone());
+
+                let config = app.config.clone();
+                let (from, to) = time_range.unwrap();
+                let evidence = window.evidence;
+                let exported_by = window.exported_by.trim().to_string();
+
+                thread::spawn(move || {
+                    let res = match evidence {
+                        true => vec![(
+                            "Evidence package".to_string(),
+                            export_evidence(&config, &cam_idxs, from, to, &exported_by)
+                                .map_err(|e| e.to_string()),
+                        )],
+                        false => export_clips(&config, &cam_idxs, from, to)
+                            .into_iter()
+                            .map(|(cam_idx, res)| (format!("Camera {}", cam_idx), res))
+                            .collect(),
+                    };
+                    *results.lock().unwrap() = Some(res);
+                });
+            }
+
+            match &results {
+                Some(None) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Exporting...");
+                    });
+                }
+                Some(Some(results)) => show_results(ui, results),
+                None => {}
+            }
+
+            // Keep the spinner going while the worker runs
+            if exporting {
+                ui.ctx().request_repaint();
+            }
+        });
+
+    if !open {
+        app.export_window = None;
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use eframe::egui::{self, Ui};
+
+use crate::{
+    config::Config,
+    locks::{add_lock, load_locks, locked_usage, remove_lock, Lock},
+    SMApp,
+};
+
+pub struct LocksWindow {
+    locks: Vec<Lock>,
+    /// Number of locked segments and their total size in bytes
+    locked_usage: (usize, u64),
+    error: Option<String>,
+    // New lock
+    cam_idx: Option<i32>,
+    date: NaiveDate,
+    from_hour: u32,
+    to_hour: u32,
+    reason: String,
+    expires: bool,
+    expires_in_days: i64,
+}
+
+impl LocksWindow {
+    pub fn load(config: &Config) -> Self {
+        let mut window = Self {
+            locks: Vec::new(),
+            locked_usage: (0, 0),
+            error: None,
+            cam_idx: None,
+            date: Local::now().date_naive(),
+            from_hour: 0,
+            to_hour: 24,
+            reason: String::new(),
+            expires: false,
+            expires_in_days: 30,
+        };
+        window.reload(config);
+
+        window
+    }
+
+    fn reload(&mut self, config: &Config) {
+        match load_locks() {
+            Ok(locks) => {
+                self.locked_usage = locked_usage(config, &locks);
+                self.locks = locks;
+            }
+            Err(error) => self.error = Some(format!("Failed to load locks: {}", error)),
+        }
+    }
+
+    fn add_lock(&mut self) -> Result<(), String> {
+        let at_hour = |hour: u32| {
+            self.date
+                .and_hms_opt(0, 0, 0)
+                .map(|d| d + chrono::Duration::hours(hour as i64))
+                .and_then(|d| Local.from_local_datetime(&d).earliest())
+                .ok_or("Invalid time".to_string())
+        };
+
+        add_lock(
+            self.cam_idx,
+            at_hour(self.from_hour)?,
+            at_hour(self.to_hour)?,
+            Some(self.reason.trim().to_string()).filter(|r| !r.is_empty()),
+            self.expires
+                .then(|| Local::now() + chrono::Duration::days(self.expires_in_days)),
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+fn show_locks(window: &LocksWindow, ui: &mut Ui) -> Option<u32> {
+    let now = Local::now();
+    let mut removed_id = None;
+
+    egui::ScrollArea::vertical()
+        .max_height(240.0)
+        .show(ui, |ui| {
+            egui::Grid::new("locks_grid")
+                .striped(true)
+                .num_columns(5)
+                .show(ui, |ui| {
+                    for lock in &window.locks {
+                        ui.label(
+                            lock.cam_idx
+                                .map(|idx| format!("Camera {}", idx))
+                                .unwrap_or("All cameras".to_string()),
+                        );
+                        ui.label(format!(
+                            "{} - {}",
+                            lock.from.format("%d.%m.%Y %H:%M"),
+                            lock.to.format("%d.%m.%Y %H:%M")
+                        ));
+                        ui.label(lock.reason.as_deref().unwrap_or(""));
+                        ui.label(match lock.expires_at {
+                            Some(_) if !lock.is_active(now) => "Expired".to_string(),
+                            Some(expires_at) => {
+                                format!("Until {}", expires_at.format("%d.%m.%Y"))
+                            }
+                            None => String::new(),
+                        });
+
+                        if ui.small_button("Remove").clicked() {
+                            removed_id = Some(lock.id);
+                        }
+
+                        ui.end_row();
+                    }
+                });
+        });
+
+    removed_id
+}
+
+fn show_new_lock(window: &mut LocksWindow, cam_idxs: &[i32], ui: &mut Ui) -> bool {
+    let mut add = false;
+
+    egui::Grid::new("new_lock_grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Camera");
+            egui::ComboBox::from_id_salt("new_lock_cam")
+                .selected_text(
+                    window
+                        .cam_idx
+                        .map(|idx| format!("Camera {}", idx))
+                        .unwrap_or("All cameras".to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut window.cam_idx, None, "All cameras");
+
+                    for cam_idx in cam_idxs {
+                        ui.selectable_value(
+                            &mut window.cam_idx,
+                            Some(*cam_idx),
+                            format!("Camera {}", cam_idx),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Day");
+            ui.add(egui_extras::DatePickerButton::new(&mut window.date).id_salt("new_lock_date"));
+            ui.end_row();
+
+            ui.label("Hours");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut window.from_hour).range(0..=23));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut window.to_hour).range(window.from_hour + 1..=24));
+            });
+            ui.end_row();
+
+            ui.label("Reason");
+            ui.text_edit_singleline(&mut window.reason);
+            ui.end_row();
+
+            ui.checkbox(&mut window.expires, "Expires after");
+            ui.add_enabled(
+                window.expires,
+                egui::DragValue::new(&mut window.expires_in_days)
+                    .range(1..=3650)
+                    .suffix(" days"),
+            );
+            ui.end_row();
+        });
+
+    if ui.button("Lock").clicked() {
+        add = true;
+    }
+
+    add
+}
+
+pub fn show_locks_window(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(window) = &mut app.locks_window else {
+        return;
+    };
+
+    let cam_idxs = app.cams.keys().copied().collect::<Vec<_>>();
+    let mut open = true;
+    let mut removed_id = None;
+    let mut add = false;
+
+    egui::Window::new("Locks")
+        .open(&mut open)
+        .default_size((640.0, 480.0))
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} locked segments holding {} MB",
+                window.locked_usage.0,
+                window.locked_usage.1 / (1024 * 1024)
+            ));
+
+            if let Some(error) = &window.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            ui.separator();
+            removed_id = show_locks(window, ui);
+            ui.separator();
+            add = show_new_lock(window, &cam_idxs, ui);
+        });
+
+    if let Some(id) = removed_id {
+        window.error = remove_lock(id).err().map(|e| e.to_string());
+        window.reload(&app.config);
+    }
+
+    if add {
+        window.error = window.add_lock().err();
+        window.reload(&app.config);
+    }
+
+    if !open {
+        app.locks_window = None;
+    }
+}
@@ -1,2 +1,8 @@
 pub mod cam_grid;
+pub mod events_panel;
+pub mod export_window;
+pub mod locks_window;
+pub mod playback_window;
+pub mod timelapse_window;
 pub mod top_menu_bar;
+pub mod zone_editor;
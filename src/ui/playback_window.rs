@@ -0,0 +1,589 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use eframe::egui::{self, pos2, vec2, Ui};
+use opencv::{core::MatTraitConst, imgcodecs};
+
+use crate::{
+    config::{Config, SegmentTag},
+    export::exportable_cam_idxs,
+    playback::{SegmentPlayer, DEFAULT_FRAME_RATE},
+    segments::{find_segment_at, segment_poster_path, segment_thumbnails_path, Segment},
+    thumbnails::{THUMBNAIL_INTERVAL_SECS, THUMBNAIL_WIDTH},
+    ui::cam_grid::{mat_to_color_image, show_grid},
+    SMApp,
+};
+
+const PLAYBACK_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+const TIMELINE_ROW_HEIGHT: f32 = 24.0;
+
+const SEGMENT_LIST_POSTER_WIDTH: f32 = 120.0;
+
+/// Thumbnail strips and posters loaded so far, `None` for ones which don't exist
+#[derive(Default)]
+struct ImageCache(HashMap<PathBuf, Option<egui::TextureHandle>>);
+
+impl ImageCache {
+    fn get(&mut self, ctx: &egui::Context, path: &Path) -> Option<&egui::TextureHandle> {
+        self.0
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let image = imgcodecs::imread(path.to_str()?, imgcodecs::IMREAD_COLOR)
+                    .ok()
+                    .filter(|image| !image.empty())?;
+
+                Some(ctx.load_texture(
+                    path.to_string_lossy(),
+                    mat_to_color_image(&image),
+                    egui::TextureOptions::default(),
+                ))
+            })
+            .as_ref()
+    }
+}
+
+/// A camera's footage and the frame currently shown of it
+struct CamPlayback {
+    player: SegmentPlayer,
+    texture: Option<egui::TextureHandle>,
+    error: Option<String>,
+}
+
+impl CamPlayback {
+    fn update_texture(&mut self, ctx: &egui::Context, at: DateTime<Local>) {
+        match self.player.frame_at(at) {
+            Ok(Some(frame)) => {
+                let image = mat_to_color_image(frame);
+
+                match &mut self.texture {
+                    Some(texture) => texture.set(image, egui::TextureOptions::default()),
+                    None => {
+                        self.texture = Some(ctx.load_texture(
+                            format!("playback-cam-{}-frame", self.player.cam_idx),
+                            image,
+                            egui::TextureOptions::default(),
+                        ))
+                    }
+                }
+                self.error = None;
+            }
+            Ok(None) => self.texture = None,
+            Err(error) => {
+                self.texture = None;
+                self.error = Some(format!("Failed to decode segment: {}", error));
+            }
+        }
+    }
+}
+
+/// Plays the selected cameras in lockstep, all showing the same wall-clock time
+pub struct PlaybackWindow {
+    /// Cameras with recordings, and whether each is selected
+    cams_selected: Vec<(i32, bool)>,
+    date: NaiveDate,
+    cams: Vec<CamPlayback>,
+    position: DateTime<Local>,
+    playing: bool,
+    speed: f32,
+    /// How many times wider than the window the timeline is
+    timeline_zoom: f32,
+    /// Position the textures were decoded at, so paused frames aren't decoded again
+    texture_position: Option<DateTime<Local>>,
+    images: ImageCache,
+}
+
+fn day_bounds(date: NaiveDate) -> (DateTime<Local>, DateTime<Local>) {
+    let at_midnight = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .unwrap()
+    };
+
+    (at_midnight(date), at_midnight(date.succ_opt().unwrap()))
+}
+
+impl PlaybackWindow {
+    pub fn new(config: &Config) -> Self {
+        let mut window = Self {
+            cams_selected: exportable_cam_idxs(config)
+                .into_iter()
+                .map(|idx| (idx, true))
+                .collect(),
+            date: Local::now().date_naive(),
+            cams: Vec::new(),
+            position: Local::now(),
+            playing: false,
+            speed: 1.0,
+            timeline_zoom: 1.0,
+            texture_position: None,
+            images: ImageCache::default(),
+        };
+        window.load_cams(config);
+
+        window
+    }
+
+    /// Reloads the segments of the selected cameras and day and seeks to the first footage
+    fn load_cams(&mut self, config: &Config) {
+        let (day_start, day_end) = day_bounds(self.date);
+
+        self.cams = self
+            .cams_selected
+            .iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(idx, _)| CamPlayback {
+                player: SegmentPlayer::new(config, *idx, day_start, day_end),
+                texture: None,
+                error: None,
+            })
+            .collect();
+        self.position = self
+            .cams
+            .iter()
+            .filter_map(|c| c.player.segments.first())
+            .map(|s| s.start.max(day_start))
+            .min()
+            .unwrap_or(day_start);
+        self.playing = false;
+        self.texture_position = None;
+        // Thumbnails of segments finalized since are picked up
+        self.images = ImageCache::default();
+    }
+
+    fn has_footage_at(&self, at: DateTime<Local>) -> bool {
+        self.cams
+            .iter()
+            .any(|c| c.player.segments.iter().any(|s| s.covers(at)))
+    }
+
+    /// Steps by the frame rate of the first camera with footage at the current position
+    fn frame_rate(&self) -> f64 {
+        self.cams
+            .iter()
+            .find_map(|c| c.player.frame_rate_at(self.position))
+            .unwrap_or(DEFAULT_FRAME_RATE)
+    }
+
+    fn step_frames(&mut self, frames: i64) {
+        self.playing = false;
+        self.position += chrono::Duration::microseconds(
+            (frames as f64 * 1_000_000.0 / self.frame_rate()) as i64,
+        );
+    }
+
+    fn advance(&mut self, elapsed_secs: f32) {
+        let (_, day_end) = day_bounds(self.date);
+
+        self.position +=
+            chrono::Duration::microseconds((elapsed_secs * self.speed * 1_000_000.0) as i64);
+
+        // Gaps where no camera has footage are skipped
+        if !self.has_footage_at(self.position) {
+            let next_footage_at = self
+                .cams
+                .iter()
+                .filter_map(|c| c.player.next_footage_at(self.position))
+                .min();
+
+            match next_footage_at {
+                Some(next_footage_at) if next_footage_at < day_end => {
+                    self.position = next_footage_at
+                }
+                _ => self.playing = false,
+            }
+        }
+
+        if self.position >= day_end {
+            self.position = day_end;
+            self.playing = false;
+        }
+    }
+
+    fn update_textures(&mut self, ctx: &egui::Context) {
+        if self.texture_position == Some(self.position) {
+            return;
+        }
+        self.texture_position = Some(self.position);
+
+        for cam in &mut self.cams {
+            cam.update_texture(ctx, self.position);
+        }
+    }
+}
+
+fn segment_color(segment: &Segment) -> egui::Color32 {
+    let tags = segment.meta.as_ref().map(|m| &m.tags[..]).unwrap_or(&[]);
+
+    if tags.contains(&SegmentTag::Motion) {
+        egui::Color32::from_rgb(220, 140, 40)
+    } else {
+        egui::Color32::from_rgb(70, 130, 180)
+    }
+}
+
+/// Shown while hovering the timeline, the thumbnail closest to the hovered moment
+fn show_thumbnail(ui: &mut Ui, images: &mut ImageCache, segment: &Segment, at: DateTime<Local>) {
+    let Some(strip) = images.get(ui.ctx(), &segment_thumbnails_path(&segment.path)) else {
+        return;
+    };
+
+    let [strip_width, strip_height] = strip.size();
+    let thumbnail_count = (strip_width as i64 / THUMBNAIL_WIDTH as i64).max(1);
+    let thumbnail_idx = ((at - segment.start).num_seconds() / THUMBNAIL_INTERVAL_SECS)
+        .clamp(0, thumbnail_count - 1);
+
+    let uv = egui::Rect::from_x_y_ranges(
+        thumbnail_idx as f32 / thumbnail_count as f32
+            ..=(thumbnail_idx + 1) as f32 / thumbnail_count as f32,
+        0.0..=1.0,
+    );
+
+    ui.add(
+        egui::Image::from_texture(strip)
+            .uv(uv)
+            .fit_to_exact_size(vec2(THUMBNAIL_WIDTH as f32, strip_height as f32)),
+    );
+}
+
+/// Draws one row of segments per camera over the day, clicking or dragging seeks and hovering
+/// previews the footage there
+fn show_timeline(
+    ui: &mut Ui,
+    images: &mut ImageCache,
+    date: NaiveDate,
+    segment_rows: &[(i32, &[Segment])],
+    position: &mut DateTime<Local>,
+    zoom: f32,
+) {
+    let (day_start, day_end) = day_bounds(date);
+    let day_secs = (day_end - day_start).num_seconds() as f32;
+
+    egui::ScrollArea::horizontal()
+        .id_salt("playback_timeline")
+        .show(ui, |ui| {
+            let size = vec2(
+                ui.available_width() * zoom,
+                TIMELINE_ROW_HEIGHT * segment_rows.len().max(1) as f32 + 16.0,
+            );
+            let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+            let painter = ui.painter_at(rect);
+
+            let x_at = |at: DateTime<Local>| {
+                rect.left() + (at - day_start).num_seconds() as f32 / day_secs * rect.width()
+            };
+
+            painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+            for (row_idx, (cam_idx, segments)) in segment_rows.iter().enumerate() {
+                let top = rect.top() + 16.0 + row_idx as f32 * TIMELINE_ROW_HEIGHT;
+
+                for segment in segments.iter() {
+                    let segment_rect = egui::Rect::from_x_y_ranges(
+                        x_at(segment.start.max(day_start))
+                            ..=x_at(segment.end().min(day_end)).max(x_at(segment.start) + 1.0),
+                        top + 2.0..=top + TIMELINE_ROW_HEIGHT - 2.0,
+                    );
+                    painter.rect_filled(segment_rect, 0.0, segment_color(segment));
+                }
+
+                painter.text(
+                    pos2(rect.left() + 2.0, top + TIMELINE_ROW_HEIGHT / 2.0),
+                    egui::Align2::LEFT_CENTER,
+                    format!("Cam {}", cam_idx),
+                    egui::FontId::proportional(11.0),
+                    ui.visuals().strong_text_color(),
+                );
+            }
+
+            // Label every hour if there is room, otherwise every 3 hours
+            let label_every = match rect.width() / 24.0 >= 40.0 {
+                true => 1,
+                false => 3,
+            };
+
+            for hour in 0..24 {
+                let x = rect.left() + hour as f32 / 24.0 * rect.width();
+
+                painter.line_segment(
+                    [pos2(x, rect.top()), pos2(x, rect.bottom())],
+                    ui.visuals().widgets.noninteractive.bg_stroke,
+                );
+
+                if hour % label_every == 0 {
+                    painter.text(
+                        pos2(x + 2.0, rect.top()),
+                        egui::Align2::LEFT_TOP,
+                        format!("{:02}:00", hour),
+                        egui::FontId::proportional(11.0),
+                        ui.visuals().weak_text_color(),
+                    );
+                }
+            }
+
+            let position_x = x_at(*position);
+            painter.line_segment(
+                [
+                    pos2(position_x, rect.top()),
+                    pos2(position_x, rect.bottom()),
+                ],
+                egui::Stroke::new(2.0, egui::Color32::RED),
+            );
+
+            let time_at = |x: f32| {
+                let fraction = ((x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+
+                day_start + chrono::Duration::milliseconds((fraction * day_secs * 1000.0) as i64)
+            };
+
+            if response.clicked() || response.dragged() {
+                if let Some(pointer_pos) = response.interact_pointer_pos() {
+                    *position = time_at(pointer_pos.x);
+                }
+            }
+
+            if let Some(hover_pos) = response.hover_pos() {
+                let at = time_at(hover_pos.x);
+                let row_idx = ((hover_pos.y - rect.top() - 16.0) / TIMELINE_ROW_HEIGHT).floor();
+                let segment = segment_rows
+                    .get(row_idx as usize)
+                    .filter(|_| row_idx >= 0.0)
+                    .and_then(|(_, segments)| find_segment_at(segments, at));
+
+                response.on_hover_ui_at_pointer(|ui| {
+                    ui.label(at.format("%H:%M:%S").to_string());
+
+                    if let Some(segment) = segment {
+                        show_thumbnail(ui, images, segment, at);
+                    }
+                });
+            }
+        });
+}
+
+fn show_playback_controls(window: &mut PlaybackWindow, ui: &mut Ui) {
+    ui.horizontal(|ui| {
+        if ui.button("⏮").on_hover_text("Previous frame").clicked() {
+            window.step_frames(-1);
+        }
+
+        if ui
+            .button(match window.playing {
+                true => "⏸ Pause",
+                false => "▶ Play",
+            })
+            .clicked()
+        {
+            window.playing = !window.playing;
+        }
+
+        if ui.button("⏭").on_hover_text("Next frame").clicked() {
+            window.step_frames(1);
+        }
+
+        egui::ComboBox::from_id_salt("playback_speed")
+            .width(64.0)
+            .selected_text(format!("{}x", window.speed))
+            .show_ui(ui, |ui| {
+                for speed in PLAYBACK_SPEEDS {
+                    ui.selectable_value(&mut window.speed, speed, format!("{}x", speed));
+                }
+            });
+
+        ui.label(window.position.format("%d.%m.%Y %H:%M:%S%.3f").to_string());
+    });
+}
+
+/// Every segment of the day with its poster frame, clicking one seeks to its start
+fn show_segment_list(window: &mut PlaybackWindow, ui: &mut Ui) {
+    let mut segments = window
+        .cams
+        .iter()
+        .flat_map(|c| &c.player.segments)
+        .collect::<Vec<_>>();
+    segments.sort_by_key(|s| s.start);
+
+    let mut seek_to = None;
+
+    egui::ScrollArea::vertical()
+        .id_salt("playback_segments")
+        .show(ui, |ui| {
+            for segment in segments {
+                ui.horizontal(|ui| {
+                    let poster_clicked = window
+                        .images
+                        .get(ui.ctx(), &segment_poster_path(&segment.path))
+                        .is_some_and(|poster| {
+                            ui.add(
+                                egui::Image::from_texture(poster)
+                                    .max_width(SEGMENT_LIST_POSTER_WIDTH)
+                                    .sense(egui::Sense::click()),
+                            )
+                            .clicked()
+                        });
+
+                    let label_clicked = ui
+                        .selectable_label(
+                            segment.covers(window.position),
+                            format!(
+                                "Camera {}\n{} - {}",
+                                segment.cam_idx,
+                                segment.start.format("%H:%M:%S"),
+                                segment.end().format("%H:%M:%S")
+                            ),
+                        )
+                        .clicked();
+
+                    if poster_clicked || label_clicked {
+                        seek_to = Some(segment.start);
+                    }
+                });
+            }
+        });
+
+    if let Some(at) = seek_to {
+        window.position = at;
+    }
+}
+
+/// Shown in place of a camera's video while it has no footage or failed to decode
+fn show_gap_placeholder(ui: &mut Ui, size: egui::Vec2, cam: &CamPlayback) {
+    let style = egui::Style::default();
+
+    ui.add_sized(
+        size,
+        egui::Label::new(
+            [
+                match &cam.error {
+                    Some(error) => egui::RichText::new(error)
+                        .size(16.0)
+                        .color(egui::Color32::RED),
+                    None => egui::RichText::new("No Footage")
+                        .size(24.0)
+                        .color(egui::Color32::LIGHT_GRAY),
+                },
+                egui::RichText::new(format!("\n Camera {}", cam.player.cam_idx))
+                    .size(16.0)
+                    .color(egui::Color32::GRAY),
+            ]
+            .into_iter()
+            .fold(egui::text::LayoutJob::default(), |mut layout_job, line| {
+                line.append_to(
+                    &mut layout_job,
+                    &style,
+                    egui::FontSelection::Default,
+                    egui::Align::Center,
+                );
+                layout_job
+            }),
+        )
+        .selectable(false),
+    );
+}
+
+pub fn show_playback_window(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(window) = &mut app.playback_window else {
+        return;
+    };
+
+    let mut open = true;
+    let mut reload = false;
+
+    if window.playing {
+        window.advance(ctx.input(|i| i.stable_dt));
+        ctx.request_repaint();
+    }
+    window.update_textures(ctx);
+
+    egui::Window::new("Playback")
+        .open(&mut open)
+        .default_size((960.0, 720.0))
+        .show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (cam_idx, selected) in &mut window.cams_selected {
+                    reload |= ui
+                        .checkbox(selected, format!("Camera {}", cam_idx))
+                        .changed();
+                }
+
+                reload |= ui
+                    .add(
+                        egui_extras::DatePickerButton::new(&mut window.date)
+                            .id_salt("playback_date"),
+                    )
+                    .changed();
+
+                ui.label("Zoom");
+                ui.add(egui::Slider::new(&mut window.timeline_zoom, 1.0..=48.0).logarithmic(true));
+            });
+
+            egui::SidePanel::right("playback_segment_list")
+                .resizable(true)
+                .default_width(280.0)
+                .show_inside(ui, |ui| show_segment_list(window, ui));
+
+            let video_size = vec2(
+                ui.available_width(),
+                (ui.available_height() - 96.0).max(120.0),
+            );
+
+            ui.allocate_ui(video_size, |ui| {
+                if window.cams.is_empty() {
+                    ui.add_sized(
+                        video_size,
+                        egui::Label::new(
+                            egui::RichText::new("No cameras selected")
+                                .size(24.0)
+                                .color(egui::Color32::GRAY),
+                        )
+                        .selectable(false),
+                    );
+                    return;
+                }
+
+                show_grid(ui, &window.cams, |ui, cam, item_size| match &cam.texture {
+                    Some(texture) => {
+                        ui.add_sized(
+                            item_size,
+                            egui::Image::from_texture(texture).fit_to_exact_size(item_size),
+                        );
+                    }
+                    None => show_gap_placeholder(ui, item_size, cam),
+                });
+            });
+
+            let segment_rows = window
+                .cams
+                .iter()
+                .map(|c| (c.player.cam_idx, &c.player.segments[..]))
+                .collect::<Vec<_>>();
+
+            show_timeline(
+                ui,
+                &mut window.images,
+                window.date,
+                &segment_rows,
+                &mut window.position,
+                window.timeline_zoom,
+            );
+
+            show_playback_controls(window, ui);
+        });
+
+    if reload {
+        window.load_cams(&app.config);
+    }
+
+    // Seeking and stepping only take effect on the next frame
+    if window.texture_position != Some(window.position) {
+        ctx.request_repaint();
+    }
+
+    if !open {
+        app.playback_window = None;
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use eframe::egui;
+
+use crate::{
+    timelapse::{generate_timelapse, TimelapseRequest},
+    utils::misc::open_path,
+    SMApp,
+};
+
+pub struct TimelapseWindow {
+    cam_idx: Option<i32>,
+    date: NaiveDate,
+    from_hour: u32,
+    /// Exclusive, 24 means the end of the day
+    to_hour: u32,
+    sample_interval_secs: u64,
+    frame_rate: usize,
+    /// Filled in by the worker thread once the timelapse is done, `None` until one was started
+    result: Option<Arc<Mutex<Option<Result<PathBuf, String>>>>>,
+}
+
+impl TimelapseWindow {
+    pub fn new(cam_idx: Option<i32>) -> Self {
+        Self {
+            cam_idx,
+            date: Local::now().date_naive(),
+            from_hour: 0,
+            to_hour: 24,
+            sample_interval_secs: 10,
+            frame_rate: 30,
+            result: None,
+        }
+    }
+
+    fn request(&self) -> Option<TimelapseRequest> {
+        let at_hour = |hour: u32| {
+            let naive = self.date.and_hms_opt(0, 0, 0)? + chrono::Duration::hours(hour as i64);
+            Local.from_local_datetime(&naive).earliest()
+        };
+
+        Some(TimelapseRequest {
+            cam_idx: self.cam_idx?,
+            from: at_hour(self.from_hour)?,
+            to: at_hour(self.to_hour)?,
+            sample_interval_secs: self.sample_interval_secs,
+            frame_rate: self.frame_rate,
+        })
+    }
+}
+
+pub fn show_timelapse_window(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(window) = &mut app.timelapse_window else {
+        return;
+    };
+
+    let cam_idxs = app.cams.keys().copied().collect::<Vec<_>>();
+    let mut open = true;
+
+    egui::Window::new("Timelapse")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("timelapse_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Camera");
+                    egui::ComboBox::from_id_salt("timelapse_cam")
+                        .selected_text(
+                            window
+                                .cam_idx
+                                .map(|idx| format!("Camera {}", idx))
+                                .unwrap_or_default(),
+                        )
+                        .show_ui(ui, |ui| {
+                            for cam_idx in cam_idxs {
+                                ui.selectable_value(
+                                    &mut window.cam_idx,
+                                    Some(cam_idx),
+                                    format!("Camera {}", cam_idx),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Day");
+                    ui.add(
+                        egui_extras::DatePickerButton::new(&mut window.date)
+                            .id_salt("timelapse_date"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Hours");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut window.from_hour).range(0..=23));
+                        ui.label("to");
+                        ui.add(
+                            egui::DragValue::new(&mut window.to_hour)
+                                .range(window.from_hour + 1..=24),
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("One frame every");
+                    ui.add(
+                        egui::DragValue::new(&mut window.sample_interval_secs)
+                            .range(1..=3600)
+                            .suffix(" s"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Frame rate");
+                    ui.add(
+                        egui::DragValue::new(&mut window.frame_rate)
+                            .range(1..=60)
+                            .suffix(" fps"),
+                    );
+                    ui.end_row();
+                });
+
+            ui.separator();
+
+            let result = window.result.as_ref().map(|r| r.lock().unwrap().clone());
+            let generating = matches!(result, Some(None));
+
+            ui.horizontal(|ui| {
+                let request = window.request();
+
+                if ui
+                    .add_enabled(
+                        request.is_some() && !generating,
+                        egui::Button::new("Generate"),
+                    )
+                    .clicked()
+                {
+                    let result = Arc::new(Mutex::new(None));
+                    window.result = Some(result.clone());
+
+                    let config = app.config.clone();
+                    let request = request.unwrap();
+
+                    thread::spawn(move || {
+                        let res = generate_timelapse(&config, &request).map_err(|e| e.to_string());
+                        *result.lock().unwrap() = Some(res);
+                    });
+                }
+
+                match result {
+                    Some(None) => {
+                        ui.spinner();
+                        ui.label("Generating...");
+                    }
+                    Some(Some(Ok(path))) => {
+                        if ui
+                            .link(path.file_name().unwrap().to_string_lossy())
+                            .clicked()
+                        {
+                            open_path(&path);
+                        }
+                    }
+                    Some(Some(Err(error))) => {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    None => {}
+                }
+            });
+
+            // Keep the spinner going while the worker runs
+            if generating {
+                ui.ctx().request_repaint();
+            }
+        });
+
+    if !open {
+        app.timelapse_window = None;
+    }
+}
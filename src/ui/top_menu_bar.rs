@@ -1,8 +1,16 @@
-use std::process::Command;
-
 use eframe::egui::{self, Ui};
 
-use crate::{config::CONFIG_PATH, SMApp};
+use crate::{
+    config::CONFIG_PATH,
+    disk_guard::{free_space_mb, space_low, writing_paused},
+    snapshots::save_all_snapshots,
+    ui::{
+        events_panel::EventsPanel, export_window::ExportWindow, locks_window::LocksWindow,
+        playback_window::PlaybackWindow, timelapse_window::TimelapseWindow,
+    },
+    utils::misc::open_path,
+    SMApp,
+};
 
 pub fn show_top_menu_bar(app: &mut SMApp, ui: &mut Ui) {
     egui::menu::bar(ui, |ui| {
@@ -19,29 +27,64 @@ pub fn show_top_menu_bar(app: &mut SMApp, ui: &mut Ui) {
         }
 
         if ui.button("Show Config File").clicked() {
-            if cfg!(target_os = "macos") {
-                Command::new("open")
-                    .arg(format!(
-                        "{}",
-                        CONFIG_PATH.parent().unwrap().as_os_str().to_string_lossy()
-                    ))
-                    .status()
-                    .unwrap();
-            } else if cfg!(target_os = "linux") {
-                // I am aware this is not right 100% of the time
-
-                Command::new("xdg-open")
-                    .arg(format!(
-                        "{}",
-                        CONFIG_PATH.parent().unwrap().as_os_str().to_string_lossy()
-                    ))
-                    .status()
-                    .unwrap();
-            }
+            open_path(CONFIG_PATH.parent().unwrap());
+        }
+
+        if ui.button("Events").clicked() {
+            app.events_panel = match app.events_panel {
+                Some(_) => None,
+                None => Some(EventsPanel::load(&app.config)),
+            };
+        }
+
+        if ui.button("Locks").clicked() {
+            app.locks_window = match app.locks_window {
+                Some(_) => None,
+                None => Some(LocksWindow::load(&app.config)),
+            };
+        }
+
+        if ui.button("Timelapse").clicked() {
+            app.timelapse_window = match app.timelapse_window {
+                Some(_) => None,
+                None => Some(TimelapseWindow::new(app.cams.keys().next().copied())),
+            };
+        }
+
+        if ui.button("Playback").clicked() {
+            app.playback_window = match app.playback_window {
+                Some(_) => None,
+                None => Some(PlaybackWindow::new(&app.config)),
+            };
+        }
+
+        if ui.button("Export").clicked() {
+            app.export_window = match app.export_window {
+                Some(_) => None,
+                None => Some(ExportWindow::new(&app.config)),
+            };
+        }
+
+        if ui.button("Snapshot All").clicked() {
+            save_all_snapshots(&app.config, &app.cams);
         }
 
         if ui.button("Add Camera").clicked() {
             todo!();
         }
+
+        if let Some(free_space_mb) = free_space_mb() {
+            if writing_paused() {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Disk full, recording paused ({} MB free)", free_space_mb),
+                );
+            } else if space_low() {
+                ui.colored_label(
+                    egui::Color32::ORANGE,
+                    format!("Disk space low ({} MB free)", free_space_mb),
+                );
+            }
+        }
     });
 }
@@ -0,0 +1,217 @@
+use eframe::egui::{self, vec2, Color32, Pos2, Rect, Sense, Shape, Stroke, Ui};
+
+use crate::{
+    config::{save_config, MotionZone, MotionZoneKind},
+    motion::MIN_ZONE_POINTS,
+    SMApp,
+};
+
+pub struct ZoneEditor {
+    pub cam_idx: i32,
+    pub zones: Vec<MotionZone>,
+    /// Zone currently being drawn, points are added by clicking on the camera preview
+    pub drawing: Option<MotionZone>,
+}
+
+impl ZoneEditor {
+    pub fn new(cam_idx: i32, zones: Vec<MotionZone>) -> Self {
+        Self {
+            cam_idx,
+            zones,
+            drawing: None,
+        }
+    }
+}
+
+fn zone_kind_label(kind: MotionZoneKind) -> &'static str {
+    match kind {
+        MotionZoneKind::Include => "Include",
+        MotionZoneKind::Ignore => "Ignore",
+    }
+}
+
+fn zone_color(kind: MotionZoneKind) -> Color32 {
+    match kind {
+        MotionZoneKind::Include => Color32::GREEN,
+        MotionZoneKind::Ignore => Color32::RED,
+    }
+}
+
+/// Draws the zones over the camera preview and lets their points be added and dragged around
+pub fn show_zone_overlay(editor: &mut ZoneEditor, ui: &mut Ui, image_response: &egui::Response) {
+    let rect = image_response.rect;
+    let to_screen = |(x, y): (f32, f32)| rect.min + vec2(x * rect.width(), y * rect.height());
+    let to_relative = |pos: Pos2| {
+        let relative = (pos - rect.min) / rect.size();
+        (relative.x.clamp(0.0, 1.0), relative.y.clamp(0.0, 1.0))
+    };
+
+    let painter = ui.painter_at(rect);
+
+    for (zone_idx, zone) in editor.zones.iter_mut().enumerate() {
+        let color = zone_color(zone.kind);
+
+        for (point_idx, point) in zone.points.iter_mut().enumerate() {
+            let handle_response = ui.interact(
+                Rect::from_center_size(to_screen(*point), vec2(12.0, 12.0)),
+                ui.id()
+                    .with(("motion_zone_point", editor.cam_idx, zone_idx, point_idx)),
+                Sense::drag(),
+            );
+
+            if handle_response.dragged() {
+                if let Some(pos) = handle_response.interact_pointer_pos() {
+                    *point = to_relative(pos);
+                }
+            }
+        }
+
+        let points = zone
+            .points
+            .iter()
+            .map(|p| to_screen(*p))
+            .collect::<Vec<_>>();
+
+        for point in &points {
+            painter.circle_filled(*point, 4.0, color);
+        }
+
+        painter.add(Shape::closed_line(points, Stroke::new(2.0, color)));
+    }
+
+    if let Some(drawing) = &mut editor.drawing {
+        if image_response.clicked() {
+            if let Some(pos) = image_response.interact_pointer_pos() {
+                drawing.points.push(to_relative(pos));
+            }
+        }
+
+        let color = zone_color(drawing.kind);
+        let points = drawing
+            .points
+            .iter()
+            .map(|p| to_screen(*p))
+            .collect::<Vec<_>>();
+
+        for point in &points {
+            painter.circle_filled(*point, 4.0, color);
+        }
+
+        painter.add(Shape::line(points, Stroke::new(2.0, color)));
+    }
+}
+
+fn show_zone_fields(ui: &mut Ui, zone: &mut MotionZone, id_salt: impl std::hash::Hash) {
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(zone_kind_label(zone.kind))
+        .show_ui(ui, |ui| {
+            for kind in [MotionZoneKind::Include, MotionZoneKind::Ignore] {
+                ui.selectable_value(&mut zone.kind, kind, zone_kind_label(kind));
+            }
+        });
+
+    let mut custom_sensitivity = zone.sensitivity.is_some();
+    if ui
+        .checkbox(&mut custom_sensitivity, "Sensitivity")
+        .changed()
+    {
+        zone.sensitivity = custom_sensitivity.then_some(1.0);
+    }
+
+    if let Some(sensitivity) = &mut zone.sensitivity {
+        ui.add(egui::Slider::new(sensitivity, 0.1..=100.0).suffix("%"));
+    }
+}
+
+pub fn show_zone_editor_window(app: &mut SMApp, ctx: &egui::Context) {
+    let Some(editor) = &mut app.zone_editor else {
+        return;
+    };
+
+    let mut open = true;
+    let mut save = false;
+
+    egui::Window::new(format!("Motion Zones - Camera {}", editor.cam_idx))
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("Click on the camera preview to add points, drag points to move them.");
+            ui.separator();
+
+            let mut removed_zone_idx = None;
+
+            for (zone_idx, zone) in editor.zones.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    show_zone_fields(ui, zone, ("motion_zone_kind", zone_idx));
+
+                    if ui.button("Remove").clicked() {
+                        removed_zone_idx = Some(zone_idx);
+                    }
+                });
+            }
+
+            if let Some(zone_idx) = removed_zone_idx {
+                editor.zones.remove(zone_idx);
+            }
+
+            let mut start_drawing = false;
+            let mut finish_drawing = false;
+            let mut cancel_drawing = false;
+
+            match &mut editor.drawing {
+                Some(drawing) => {
+                    ui.horizontal(|ui| {
+                        show_zone_fields(ui, drawing, "motion_zone_drawing_kind");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} points", drawing.points.len()));
+
+                        finish_drawing = ui
+                            .add_enabled(
+                                drawing.points.len() >= MIN_ZONE_POINTS,
+                                egui::Button::new("Finish"),
+                            )
+                            .clicked();
+                        cancel_drawing = ui.button("Cancel").clicked();
+                    });
+                }
+                None => {
+                    start_drawing = ui.button("New Zone").clicked();
+                }
+            }
+
+            if start_drawing {
+                editor.drawing = Some(MotionZone::default());
+            } else if finish_drawing {
+                editor.zones.extend(editor.drawing.take());
+            } else if cancel_drawing {
+                editor.drawing = None;
+            }
+
+            ui.separator();
+
+            save = ui.button("Save").clicked();
+        });
+
+    if save {
+        if let Some(device_config) = app
+            .config
+            .video_devices
+            .iter_mut()
+            .find(|c| c.idx == editor.cam_idx)
+        {
+            device_config.motion_zones = editor.zones.clone();
+        }
+
+        save_config(&app.config);
+
+        if let Some((cam, _)) = app.cams.get(&editor.cam_idx) {
+            *cam.motion_zones.write().unwrap() = editor.zones.clone();
+        }
+    }
+
+    if !open {
+        app.zone_editor = None;
+    }
+}
@@ -1,7 +1,18 @@
-use std::error::Error as StdError;
+use std::{error::Error as StdError, path::Path, process::Command};
 
 use anyhow::anyhow;
 
 pub fn sendable_anyhow(msg: String) -> Box<dyn StdError + Send> {
     anyhow!(msg).into()
 }
+
+/// Opens a file or directory with the OS' default application
+pub fn open_path(path: &Path) {
+    if cfg!(target_os = "macos") {
+        Command::new("open").arg(path).status().unwrap();
+    } else if cfg!(target_os = "linux") {
+        // I am aware this is not right 100% of the time
+
+        Command::new("xdg-open").arg(path).status().unwrap();
+    }
+}
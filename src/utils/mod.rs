@@ -1,2 +1,3 @@
 pub mod misc;
+pub mod sun;
 pub mod video;
@@ -0,0 +1,116 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Julian date of 2000-01-01 12:00 UTC
+const J2000: f64 = 2451545.0;
+
+/// Julian date of the unix epoch
+const JULIAN_UNIX_EPOCH: f64 = 2440587.5;
+
+fn julian_to_utc(julian: f64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(((julian - JULIAN_UNIX_EPOCH) * 86400.0).round() as i64, 0)
+}
+
+/// Computes sunrise and sunset for the given date using the sunrise equation, this is accurate
+/// to within a minute or two which is plenty for scheduling. Returns `None` during polar day or
+/// polar night, when the sun doesn't rise or set at all.
+pub fn sunrise_sunset(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let days_since_j2000 = (date - NaiveDate::from_ymd_opt(2000, 1, 1)?).num_days() as f64;
+
+    // Mean solar time at the given longitude
+    let mean_solar_time = days_since_j2000 - longitude / 360.0;
+
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let mean_anomaly_rad = mean_anomaly.to_radians();
+
+    let equation_of_center = 1.9148 * mean_anomaly_rad.sin()
+        + 0.02 * (2.0 * mean_anomaly_rad).sin()
+        + 0.0003 * (3.0 * mean_anomaly_rad).sin();
+
+    let ecliptic_longitude =
+        (mean_anomaly + equation_of_center + 180.0 + 102.9372).rem_euclid(360.0);
+    let ecliptic_longitude_rad = ecliptic_longitude.to_radians();
+
+    let solar_transit = J2000 + mean_solar_time + 0.0053 * mean_anomaly_rad.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude_rad).sin();
+
+    let declination_sin = ecliptic_longitude_rad.sin() * 23.4397_f64.to_radians().sin();
+    let declination_cos = declination_sin.asin().cos();
+
+    let latitude_rad = latitude.to_radians();
+
+    // -0.833° accounts for atmospheric refraction and the size of the sun's disc
+    let hour_angle_cos = ((-0.833_f64).to_radians().sin() - latitude_rad.sin() * declination_sin)
+        / (latitude_rad.cos() * declination_cos);
+
+    if !(-1.0..=1.0).contains(&hour_angle_cos) {
+        return None;
+    }
+
+    let hour_angle = hour_angle_cos.acos().to_degrees();
+
+    Some((
+        julian_to_utc(solar_transit - hour_angle / 360.0)?,
+        julian_to_utc(solar_transit + hour_angle / 360.0)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+
+    const TROMSO: (f64, f64) = (69.6492, 18.9553);
+
+    fn assert_close(actual: DateTime<Utc>, expected: DateTime<Utc>) {
+        let diff_secs = (actual - expected).num_seconds().abs();
+
+        assert!(
+            diff_secs <= 2 * 60,
+            "{} is {}s off from {}",
+            actual,
+            diff_secs,
+            expected
+        );
+    }
+
+    #[test]
+    fn matches_reference_times_in_london() {
+        // Published sunrise and sunset times for London, converted to UTC
+        let cases = [
+            (
+                NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(),
+                Utc.with_ymd_and_hms(2024, 6, 21, 3, 43, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 6, 21, 20, 21, 0).unwrap(),
+            ),
+            (
+                NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+                Utc.with_ymd_and_hms(2024, 12, 21, 8, 4, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 12, 21, 15, 53, 0).unwrap(),
+            ),
+        ];
+
+        for (date, expected_sunrise, expected_sunset) in cases {
+            let (sunrise, sunset) = sunrise_sunset(date, LONDON.0, LONDON.1).unwrap();
+
+            assert_close(sunrise, expected_sunrise);
+            assert_close(sunset, expected_sunset);
+        }
+    }
+
+    #[test]
+    fn polar_day_and_night_have_no_sunrise() {
+        for date in [
+            NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+        ] {
+            assert_eq!(sunrise_sunset(date, TROMSO.0, TROMSO.1), None);
+        }
+    }
+}
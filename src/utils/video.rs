@@ -1,9 +1,80 @@
-use std::{error::Error as StdError, fs, path::PathBuf, process::Command, sync::Mutex};
+use std::{
+    error::Error as StdError,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
 
 use opencv::core::Mat;
 
 use anyhow::anyhow;
 
+/// Runs ffmpeg and logs its output, errors if it didn't exit successfully
+pub fn run_ffmpeg(current_dir: Option<&Path>, args: &[&str]) -> Result<(), Box<dyn StdError>> {
+    let mut command = Command::new("ffmpeg");
+
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+
+    let output = command.args(args).output()?;
+
+    println!(
+        "ffmpeg output ({}): {}\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    if !output.status.success() {
+        return Err(anyhow!("ffmpeg exited with {}", output.status).into());
+    }
+
+    Ok(())
+}
+
+/// Re-encodes a video into an mp4 at `output`, scaled down to at most `max_width` and with
+/// frames dropped down to `frame_rate`. Anything left out is kept as it is.
+pub fn reencode_video(
+    input: &Path,
+    output: &Path,
+    max_width: Option<u16>,
+    frame_rate: Option<usize>,
+    bitrate_kbps: Option<u32>,
+) -> Result<(), Box<dyn StdError>> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+    ];
+
+    if let Some(max_width) = max_width {
+        // -2 keeps the aspect ratio while making sure the height stays even for yuv420p
+        args.extend([
+            "-vf".to_string(),
+            format!("scale='min({},iw)':-2", max_width),
+        ]);
+    }
+
+    if let Some(frame_rate) = frame_rate {
+        args.extend(["-r".to_string(), frame_rate.to_string()]);
+    }
+
+    if let Some(bitrate_kbps) = bitrate_kbps {
+        args.extend(["-b:v".to_string(), format!("{}k", bitrate_kbps)]);
+    }
+
+    args.extend(
+        ["-c:v", "libx264", "-pix_fmt", "yuv420p", "-f", "mp4"]
+            .into_iter()
+            .map(String::from),
+    );
+    args.push(output.to_string_lossy().to_string());
+
+    run_ffmpeg(None, &args.iter().map(String::as_str).collect::<Vec<_>>())
+}
+
 #[derive(Debug)]
 pub struct VideoWriter {
     path: PathBuf,
@@ -40,15 +111,24 @@ impl VideoWriter {
         Ok(())
     }
 
-    pub fn finish(&mut self) -> Result<(), Box<dyn StdError>> {
+    pub fn frame_count(&self) -> usize {
+        self.frame_idx
+    }
+
+    pub fn frame_rate(&self) -> usize {
+        self.frame_rate
+    }
+
+    /// Encodes the written frames into an mp4 next to the frames directory and returns its path
+    pub fn finish(&mut self) -> Result<PathBuf, Box<dyn StdError>> {
         let chunk_name = self.path.file_name().unwrap().to_str().unwrap();
         let mut template_frame_path = PathBuf::new();
         template_frame_path.push(chunk_name);
         template_frame_path.push("%d.bmp");
 
-        let output = Command::new("ffmpeg")
-            .current_dir(self.path.parent().unwrap())
-            .args([
+        run_ffmpeg(
+            self.path.parent(),
+            &[
                 "-framerate",
                 self.frame_rate.to_string().as_str(),
                 "-start_number",
@@ -60,18 +140,17 @@ impl VideoWriter {
                 "-pix_fmt",
                 "yuv420p",
                 format!("{}.mp4", chunk_name).as_str(),
-            ])
-            .output()?;
-
-        println!(
-            "ffmpeg output ({}): {}\n{}",
-            output.status,
-            String::from_utf8_lossy(&output.stderr),
-            String::from_utf8_lossy(&output.stdout)
-        );
+            ],
+        )
+        // Keep the frames around if encoding failed so the footage isn't lost
+        .map_err(|error| anyhow!("{} for {:?}", error, self.path))?;
 
         fs::remove_dir_all(&self.path)?;
 
-        Ok(())
+        Ok(self
+            .path
+            .parent()
+            .unwrap()
+            .join(format!("{}.mp4", chunk_name)))
     }
 }
@@ -1,68 +1,109 @@
 use std::{
     error::Error as StdError,
-    fs,
     path::PathBuf,
-    sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, RwLock,
+    },
     thread,
     time::Duration,
 };
 
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
 use opencv::{
-    core::{Mat, MatTraitConst},
+    core::{Mat, MatTraitConst, Size},
     videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
 };
 
 use crate::{
-    config::{Config, VideoDeviceConfig},
-    utils::{misc::sendable_anyhow, video::VideoWriter},
+    config::{Config, MotionZone, RecordingMode, SegmentTag, VideoDeviceConfig},
+    disk_guard::writing_paused,
+    events::{log_event, EventKind},
+    motion::{MotionState, MotionTrigger},
+    overlay::draw_overlay,
+    privacy::apply_privacy_masks,
+    recorder::RecordingWorker,
+    schedule::recording_mode_at,
+    segments::{cam_recordings_dir, segment_name, MAX_SEGMENT_SECS},
+    transform::{apply_transform, transformed_size},
+    utils::misc::sendable_anyhow,
 };
 
 #[derive(Debug)]
 pub struct VideoCam {
     pub config: VideoDeviceConfig,
+    /// Preview frame shown in the grid, scaled down and with the preview overlay drawn on
     pub frame: RwLock<(usize, Mat)>,
+    /// Full resolution frame after the transform and privacy masks without any overlay,
+    /// snapshots are taken from this
+    pub full_frame: RwLock<Mat>,
     pub errored: AtomicBool,
+    pub dropped_frames: AtomicUsize,
+    /// Kept separately from the config so zones can be edited while the camera is running
+    pub motion_zones: RwLock<Vec<MotionZone>>,
+    /// Toggled from the UI at runtime, capture being off releases the device entirely
+    pub capture_enabled: AtomicBool,
+    pub recording_enabled: AtomicBool,
+    /// Set while recording was turned on by hand from the UI, segments recorded meanwhile are
+    /// tagged as manual
+    pub recording_started_manually: AtomicBool,
+    /// Whether frames are currently being written to a segment, as opposed to e.g. waiting for
+    /// motion
+    pub recording: AtomicBool,
 }
 
 impl VideoCam {
     pub fn new(config: VideoDeviceConfig) -> Self {
         Self {
+            motion_zones: RwLock::new(config.motion_zones.clone()),
+            recording_enabled: AtomicBool::new(config.recording.enabled),
+            recording_started_manually: AtomicBool::new(false),
             config,
             frame: RwLock::new((0, Mat::default())),
+            full_frame: RwLock::new(Mat::default()),
             errored: AtomicBool::new(false),
+            dropped_frames: AtomicUsize::new(0),
+            capture_enabled: AtomicBool::new(true),
+            recording: AtomicBool::new(false),
         }
     }
 }
 
-fn get_video_chunk_path(app_config: &Config, cam: Arc<VideoCam>) -> PathBuf {
-    let mut path = app_config.recordings_dir.clone();
-
-    path.extend([
-        format!("cam-{}", cam.config.idx),
-        format!("rec-{}", chrono::Local::now().format("%d.%m.%Y-%H.%M.%S")),
-    ]);
+/// Frames directory of a segment starting with a frame captured at `start`
+pub fn get_video_chunk_path(
+    app_config: &Config,
+    cam: Arc<VideoCam>,
+    mut start: DateTime<Local>,
+) -> PathBuf {
+    let cam_dir = cam_recordings_dir(&app_config.recordings_dir, cam.config.idx);
+
+    // Two segments must never share a directory, finishing one would delete the other's frames
+    while cam_dir.join(segment_name(start)).exists()
+        || cam_dir
+            .join(format!("{}.mp4", segment_name(start)))
+            .exists()
+    {
+        start += chrono::Duration::milliseconds(1);
+    }
 
-    path
+    cam_dir.join(segment_name(start))
 }
 
-fn save_video_chunk(
-    cam: Arc<VideoCam>,
-    video_writer: Arc<Mutex<VideoWriter>>,
-    frames: Vec<Mat>,
-) -> Result<(), Box<dyn StdError + Send>> {
-    let mut video_writer = video_writer.lock().unwrap();
+/// Size of a `cam_size` frame scaled down to at most `max_width`, keeping its aspect ratio
+fn scaled_frame_size(cam_size: (u32, u32), max_width: Option<u16>) -> Size {
+    let max_frame_width = max_width.map(|r| r as f32).unwrap_or(cam_size.0 as f32);
+    let resized_height = ((max_frame_width / cam_size.0 as f32) * cam_size.1 as f32) as i32;
 
-    for frame in frames {
-        video_writer.write(&frame).map_err(|_| {
-            sendable_anyhow(format!(
-                "Failed to write frame for video device {}",
-                cam.config.idx
-            ))
-        })?;
+    Size {
+        width: max_frame_width as i32,
+        height: resized_height,
     }
+}
 
-    Ok(())
+fn frame_size(frame: &Mat) -> Result<Size, Box<dyn StdError + Send>> {
+    frame
+        .size()
+        .map_err(|_| sendable_anyhow("Failed to get frame size".to_string()))
 }
 
 fn capture_video_(app_config: Config, cam: Arc<VideoCam>) -> Result<(), Box<dyn StdError + Send>> {
@@ -104,36 +145,77 @@ fn capture_video_(app_config: Config, cam: Arc<VideoCam>) -> Result<(), Box<dyn
         )));
     }
 
-    let mut video_writer: Option<Arc<Mutex<VideoWriter>>> = match cam.config.recording.enabled {
-        true => {
-            let path = get_video_chunk_path(&app_config, cam.clone());
-            fs::create_dir_all(&path).unwrap();
-            Some(Arc::new(Mutex::new(VideoWriter::new(
-                path,
-                cam_fps.round() as usize,
-            ))))
-        }
-        false => None,
+    log_event(
+        EventKind::CameraConnected,
+        Some(cam.config.idx),
+        format!("{}x{} at {} fps", cam_size.0, cam_size.1, cam_fps),
+    );
+
+    // Everything after capture works with the rotated and cropped frame
+    let cam_size = {
+        let size = transformed_size(
+            Size::new(cam_size.0 as i32, cam_size.1 as i32),
+            &cam.config.transform,
+        );
+
+        (size.width as u32, size.height as u32)
     };
 
+    // Only spawned once recording is enabled, which can happen at any point from the UI
+    let mut recording_worker: Option<RecordingWorker> = None;
+
+    let location = app_config.location;
+    let mut recording_mode = recording_mode_at(&cam.config.recording, location, Local::now());
+    let mut motion_trigger = MotionTrigger::new(&cam.config.recording.motion, cam_fps);
+    let pre_roll_frames = (cam.config.recording.motion.pre_roll_secs as f64 * cam_fps) as usize;
+
     let mut frame_idx: usize = 0;
+    let mut segment_frames_count: usize = 0;
 
-    // ~2 seconds of frames
-    let frame_buf_len = (cam_fps * 2.0) as usize;
+    // ~2 seconds of frames, or however many are needed to cover the motion pre-roll
+    let frame_buf_len = ((cam_fps * 2.0) as usize).max(pre_roll_frames + 1);
     let mut frames_buf: Vec<Mat> = (0..frame_buf_len)
         .map(|_| Mat::default())
         .collect::<Vec<_>>();
-    let full_clip_of_frames_count = (cam_fps * 60.0 * 4.0) as usize; // 4 min
-
-    let max_frame_width = cam
-        .config
-        .max_resolution_width
-        .map(|r| r as f32)
-        .unwrap_or(cam_size.0 as f32);
+    let mut frame_times_buf: Vec<DateTime<Local>> = vec![Local::now(); frame_buf_len];
+    let full_clip_of_frames_count = (cam_fps * MAX_SEGMENT_SECS as f64) as usize;
 
-    let resized_height = ((max_frame_width / cam_size.0 as f32) * cam_size.1 as f32) as i32;
+    let recording_size = scaled_frame_size(cam_size, cam.config.recording_max_width());
+    let preview_size = scaled_frame_size(cam_size, cam.config.preview_max_width());
 
     loop {
+        if !cam.capture_enabled.load(atomic::Ordering::Relaxed) {
+            if let Some(recording_worker) = &recording_worker {
+                if segment_frames_count > 0 {
+                    recording_worker.finish_segment();
+                    segment_frames_count = 0;
+                }
+            }
+            cam.recording.store(false, atomic::Ordering::Relaxed);
+
+            vid_cap.release().map_err(|_| {
+                sendable_anyhow(format!("Failed to release video device {}", cam.config.idx))
+            })?;
+
+            while !cam.capture_enabled.load(atomic::Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(200));
+            }
+
+            if !vid_cap
+                .open(cam.config.idx, videoio::CAP_ANY)
+                .map_err(|_| {
+                    sendable_anyhow(format!("Failed to open video device {}", cam.config.idx))
+                })?
+            {
+                return Err(sendable_anyhow(format!(
+                    "Failed to reopen video device {}",
+                    cam.config.idx
+                )));
+            }
+
+            motion_trigger = MotionTrigger::new(&cam.config.recording.motion, cam_fps);
+        }
+
         if !vid_cap.is_opened().map_err(|_| {
             sendable_anyhow(format!(
                 "Failed to check if video device {} is open",
@@ -161,68 +243,195 @@ fn capture_video_(app_config: Config, cam: Arc<VideoCam>) -> Result<(), Box<dyn
             )));
         }
 
-        opencv::imgproc::resize(
-            &frames_buf[frame_idx % frame_buf_len].clone(),
+        frame_times_buf[frame_idx % frame_buf_len] = Local::now();
+
+        apply_transform(
+            &mut frames_buf[frame_idx % frame_buf_len],
+            &cam.config.transform,
+        )
+        .map_err(|_| sendable_anyhow("Failed to transform frame".to_string()))?;
+
+        // Masks go on before the frame is used for anything else so masked regions are never
+        // shown, recorded or exported. They are relative to the transformed frame, same as what
+        // the preview shows
+        apply_privacy_masks(
             &mut frames_buf[frame_idx % frame_buf_len],
-            opencv::core::Size {
-                width: max_frame_width as i32,
-                height: resized_height,
-            },
-            0.0,
-            0.0,
-            opencv::imgproc::InterpolationFlags::INTER_NEAREST as i32,
+            &cam.config.privacy_masks,
         )
-        .map_err(|_| sendable_anyhow("Failed to resize frame".to_string()))?;
+        .map_err(|_| sendable_anyhow("Failed to apply privacy masks".to_string()))?;
+
+        frames_buf[frame_idx % frame_buf_len]
+            .copy_to(&mut *cam.full_frame.write().unwrap())
+            .map_err(|_| sendable_anyhow("Failed to copy full frame".to_string()))?;
 
         {
             let mut frame = cam.frame.write().unwrap();
             frame.0 += 1;
 
-            frames_buf[frame_idx % frame_buf_len]
-                .copy_to(&mut frame.1)
-                .map_err(|_| {
+            let buf_frame = &frames_buf[frame_idx % frame_buf_len];
+
+            // The preview is scaled from the full frame separately from the recording, so the
+            // grid can show a cheaper stream than what gets recorded or the other way around
+            if preview_size == frame_size(buf_frame)? {
+                buf_frame.copy_to(&mut frame.1).map_err(|_| {
                     sendable_anyhow("Failed to copy frame to idx_and_frame".to_string())
                 })?;
+            } else {
+                opencv::imgproc::resize(
+                    buf_frame,
+                    &mut frame.1,
+                    preview_size,
+                    0.0,
+                    0.0,
+                    opencv::imgproc::InterpolationFlags::INTER_AREA as i32,
+                )
+                .map_err(|_| sendable_anyhow("Failed to resize preview frame".to_string()))?;
+            }
+
+            if cam.config.overlay.preview {
+                draw_overlay(
+                    &mut frame.1,
+                    &cam.config,
+                    frame_times_buf[frame_idx % frame_buf_len],
+                )
+                .map_err(|_| sendable_anyhow("Failed to draw preview overlay".to_string()))?;
+            }
         }
 
-        if frame_idx > 0 && frame_idx % frame_buf_len == (frame_buf_len - 1) {
-            if let Some(video_writer) = video_writer.clone() {
-                let frames_buf = frames_buf.clone();
-                let cam = cam.clone();
+        if recording_size != frame_size(&frames_buf[frame_idx % frame_buf_len])? {
+            opencv::imgproc::resize(
+                &frames_buf[frame_idx % frame_buf_len].clone(),
+                &mut frames_buf[frame_idx % frame_buf_len],
+                recording_size,
+                0.0,
+                0.0,
+                opencv::imgproc::InterpolationFlags::INTER_NEAREST as i32,
+            )
+            .map_err(|_| sendable_anyhow("Failed to resize frame".to_string()))?;
+        }
 
-                thread::spawn(move || {
-                    let res = save_video_chunk(cam, video_writer.clone(), frames_buf);
+        // Running out of disk space stops recording the same way turning it off does
+        let recording_enabled =
+            cam.recording_enabled.load(atomic::Ordering::Relaxed) && !writing_paused();
 
-                    if let Err(error) = res {
-                        println!("Failed to save video chunk: {}", error);
-                    }
-                });
+        if recording_enabled && recording_worker.is_none() {
+            recording_worker = Some(RecordingWorker::spawn(
+                app_config.clone(),
+                cam.clone(),
+                cam_fps.round() as usize,
+            ));
+        }
+
+        // The worker is kept around when recording gets disabled, only the segment is finished
+        if !recording_enabled && segment_frames_count > 0 {
+            if let Some(recording_worker) = &recording_worker {
+                recording_worker.finish_segment();
             }
+            segment_frames_count = 0;
+            motion_trigger = MotionTrigger::new(&cam.config.recording.motion, cam_fps);
         }
 
-        frame_idx += 1;
+        if let Some(recording_worker) = recording_worker.as_ref().filter(|_| recording_enabled) {
+            let frame = &frames_buf[frame_idx % frame_buf_len];
+
+            let scheduled_recording_mode =
+                recording_mode_at(&cam.config.recording, location, Local::now());
+
+            // Start a fresh segment whenever the schedule switches modes so a segment only ever
+            // contains footage recorded in a single mode
+            if scheduled_recording_mode != recording_mode {
+                println!(
+                    "Recording mode for video device {} changed from {:?} to {:?}",
+                    cam.config.idx, recording_mode, scheduled_recording_mode
+                );
 
-        if frame_idx == full_clip_of_frames_count {
-            frame_idx = 0;
+                recording_mode = scheduled_recording_mode;
+                motion_trigger = MotionTrigger::new(&cam.config.recording.motion, cam_fps);
 
-            if let Some(video_writer_) = video_writer.clone() {
-                thread::spawn(move || {
-                    let res = video_writer_.lock().unwrap().finish();
+                if segment_frames_count > 0 {
+                    recording_worker.finish_segment();
+                    segment_frames_count = 0;
+                }
+            }
 
-                    if let Err(error) = res {
-                        println!("Failed to finalize video clip: {}", error);
+            let motion_state = match recording_mode {
+                RecordingMode::Off => MotionState::Idle,
+                RecordingMode::Continuous => MotionState::Ongoing,
+                RecordingMode::Motion => motion_trigger
+                    .update(frame, &cam.motion_zones.read().unwrap())
+                    .map_err(|_| {
+                        sendable_anyhow(format!(
+                            "Failed to detect motion for video device {}",
+                            cam.config.idx
+                        ))
+                    })?,
+            };
+
+            cam.recording.store(
+                matches!(motion_state, MotionState::Started | MotionState::Ongoing),
+                atomic::Ordering::Relaxed,
+            );
+
+            match motion_state {
+                MotionState::Idle => {}
+                MotionState::Started | MotionState::Ongoing => {
+                    if segment_frames_count == 0 {
+                        recording_worker.tag_segment(match recording_mode {
+                            RecordingMode::Motion => SegmentTag::Motion,
+                            _ => SegmentTag::Continuous,
+                        });
+
+                        if cam
+                            .recording_started_manually
+                            .load(atomic::Ordering::Relaxed)
+                        {
+                            recording_worker.tag_segment(SegmentTag::Manual);
+                        }
                     }
-                });
 
-                let new_video_writer_path = get_video_chunk_path(&app_config, cam.clone());
-                fs::create_dir_all(&new_video_writer_path).unwrap();
+                    if motion_state == MotionState::Started {
+                        log_event(EventKind::MotionStart, Some(cam.config.idx), "");
+
+                        let pre_roll_start = frame_times_buf[frame_idx % frame_buf_len]
+                            - chrono::Duration::milliseconds(
+                                (cam.config.recording.motion.pre_roll_secs * 1000.0) as i64,
+                            );
+
+                        // The ring buffer still holds the frames from right before the motion
+                        for back_idx in (1..=pre_roll_frames.min(frame_idx)).rev() {
+                            let buf_idx = (frame_idx - back_idx) % frame_buf_len;
+
+                            // Frames from before capture was last turned off aren't pre-roll
+                            if frame_times_buf[buf_idx] < pre_roll_start {
+                                continue;
+                            }
 
-                video_writer = Some(Arc::new(Mutex::new(VideoWriter::new(
-                    new_video_writer_path,
-                    cam_fps as usize,
-                ))));
+                            recording_worker
+                                .write_frame(&frames_buf[buf_idx], frame_times_buf[buf_idx]);
+                            segment_frames_count += 1;
+                        }
+                    }
+
+                    recording_worker.write_frame(frame, frame_times_buf[frame_idx % frame_buf_len]);
+                    segment_frames_count += 1;
+                }
+                MotionState::Ended => {
+                    log_event(EventKind::MotionEnd, Some(cam.config.idx), "");
+
+                    recording_worker.finish_segment();
+                    segment_frames_count = 0;
+                }
             }
+
+            if segment_frames_count >= full_clip_of_frames_count {
+                recording_worker.finish_segment();
+                segment_frames_count = 0;
+            }
+        } else {
+            cam.recording.store(false, atomic::Ordering::Relaxed);
         }
+
+        frame_idx += 1;
     }
 }
 
@@ -242,8 +451,13 @@ pub fn capture_video(
 
         try_count += 1;
 
-        if last_try_res.is_ok() {
-            return Ok(());
+        match &last_try_res {
+            Ok(()) => return Ok(()),
+            Err(error) => log_event(
+                EventKind::CameraDisconnected,
+                Some(cam.config.idx),
+                error.to_string(),
+            ),
         }
 
         if (Utc::now() - last_try_at) > chrono::Duration::hours(2) {